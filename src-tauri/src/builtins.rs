@@ -0,0 +1,341 @@
+// Built-in templates that can be updated after they were first installed.
+//
+// `<templates>/.builtins.json` records, per built-in, the version installed
+// and a hash of the content written. A template whose file still matches that
+// hash is unmodified and may be updated; a missing file was deleted by the
+// user and is only written again on request.
+
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::Path;
+
+use crate::templates;
+
+const STATE_FILE: &str = ".builtins.json";
+/// Written by earlier versions after seeding the defaults once.
+const LEGACY_SENTINEL_FILE: &str = ".defaults_initialized";
+
+// -----------------
+// Types
+// -----------------
+pub struct BuiltinTemplate {
+    pub name: &'static str,
+    /// Bumped whenever `content` changes.
+    pub version: u32,
+    pub content: &'static str,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct InstalledBuiltin {
+    version: u32,
+    hash: String,
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct BuiltinState {
+    templates: BTreeMap<String, InstalledBuiltin>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub enum BuiltinStatus {
+    UpToDate,
+    UpdateAvailable,
+    /// Edited by the user; never updated automatically.
+    Modified,
+    Deleted,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct BuiltinTemplateInfo {
+    pub name: String,
+    pub installed_version: u32,
+    pub available_version: u32,
+    pub status: BuiltinStatus,
+}
+
+// -----------------
+// State
+// -----------------
+
+/// FNV-1a, which unlike `DefaultHasher` is stable across Rust releases.
+fn content_hash(content: &str) -> String {
+    let hash = content.bytes().fold(0xcbf29ce484222325u64, |hash, byte| {
+        (hash ^ u64::from(byte)).wrapping_mul(0x100000001b3)
+    });
+    format!("{:016x}", hash)
+}
+
+fn read_state(templates_dir: &Path) -> Result<Option<BuiltinState>, String> {
+    match fs::read_to_string(templates_dir.join(STATE_FILE)) {
+        Ok(json) => serde_json::from_str(&json)
+            .map(Some)
+            .map_err(|e| format!("Invalid {}: {}", STATE_FILE, e)),
+        Err(_) => Ok(None),
+    }
+}
+
+fn write_state(templates_dir: &Path, state: &BuiltinState) -> Result<(), String> {
+    let json = serde_json::to_string_pretty(state).map_err(|e| e.to_string())?;
+    templates::write_atomic(&templates_dir.join(STATE_FILE), json.as_bytes())
+        .map_err(|e| e.to_string())
+}
+
+fn install(
+    templates_dir: &Path,
+    state: &mut BuiltinState,
+    builtin: &BuiltinTemplate,
+) -> Result<(), String> {
+    templates::save_template(templates_dir, None, builtin.name, builtin.content)?;
+    state.templates.insert(
+        builtin.name.to_string(),
+        InstalledBuiltin {
+            version: builtin.version,
+            hash: content_hash(builtin.content),
+        },
+    );
+    Ok(())
+}
+
+/// Installs built-ins that have never been installed. Deleted ones stay
+/// deleted, and a user template with the same name is left alone.
+pub fn sync(templates_dir: &Path, builtins: &[BuiltinTemplate]) -> Result<(), String> {
+    let legacy_seeded = templates_dir.join(LEGACY_SENTINEL_FILE).exists();
+    let mut state = read_state(templates_dir)?.unwrap_or_default();
+    let mut changed = false;
+
+    for builtin in builtins {
+        if state.templates.contains_key(builtin.name) {
+            continue;
+        }
+        let path = templates::template_path(templates_dir, None, builtin.name)?;
+        if path.exists() || legacy_seeded {
+            // Seeded before versions were tracked, or a user template of the
+            // same name. A seeded file is taken as the first version as it is
+            // on disk, so it can be updated; a user template only counts as
+            // unmodified when identical to the current content. A missing
+            // file was deleted by the user.
+            let on_disk = fs::read_to_string(&path).ok();
+            let current = on_disk.as_deref() == Some(builtin.content);
+            let hash = match &on_disk {
+                Some(content) if legacy_seeded => content_hash(content),
+                _ => content_hash(builtin.content),
+            };
+            state.templates.insert(
+                builtin.name.to_string(),
+                InstalledBuiltin {
+                    version: if current { builtin.version } else { 1 },
+                    hash,
+                },
+            );
+        } else {
+            install(templates_dir, &mut state, builtin)?;
+        }
+        changed = true;
+    }
+
+    if changed {
+        write_state(templates_dir, &state)?;
+    }
+    if legacy_seeded {
+        let _ = fs::remove_file(templates_dir.join(LEGACY_SENTINEL_FILE));
+    }
+    Ok(())
+}
+
+// -----------------
+// Updates
+// -----------------
+pub fn list(
+    templates_dir: &Path,
+    builtins: &[BuiltinTemplate],
+) -> Result<Vec<BuiltinTemplateInfo>, String> {
+    let state = read_state(templates_dir)?.unwrap_or_default();
+    let mut infos = Vec::with_capacity(builtins.len());
+    for builtin in builtins {
+        let Some(installed) = state.templates.get(builtin.name) else {
+            continue;
+        };
+        let path = templates::template_path(templates_dir, None, builtin.name)?;
+        let status = match fs::read_to_string(&path) {
+            Err(_) => BuiltinStatus::Deleted,
+            Ok(content) if content_hash(&content) != installed.hash => BuiltinStatus::Modified,
+            Ok(_) if installed.version < builtin.version => BuiltinStatus::UpdateAvailable,
+            Ok(_) => BuiltinStatus::UpToDate,
+        };
+        infos.push(BuiltinTemplateInfo {
+            name: builtin.name.to_string(),
+            installed_version: installed.version,
+            available_version: builtin.version,
+            status,
+        });
+    }
+    Ok(infos)
+}
+
+/// Updates unmodified built-ins to their latest version, all of them or only
+/// those in `names`. Returns the names updated.
+pub fn apply_updates(
+    templates_dir: &Path,
+    builtins: &[BuiltinTemplate],
+    names: Option<&[String]>,
+) -> Result<Vec<String>, String> {
+    let outdated: Vec<String> = list(templates_dir, builtins)?
+        .into_iter()
+        .filter(|info| info.status == BuiltinStatus::UpdateAvailable)
+        .filter(|info| names.map_or(true, |names| names.contains(&info.name)))
+        .map(|info| info.name)
+        .collect();
+    if outdated.is_empty() {
+        return Ok(outdated);
+    }
+
+    let mut state = read_state(templates_dir)?.unwrap_or_default();
+    for builtin in builtins
+        .iter()
+        .filter(|b| outdated.iter().any(|n| n == b.name))
+    {
+        install(templates_dir, &mut state, builtin)?;
+    }
+    write_state(templates_dir, &state)?;
+    Ok(outdated)
+}
+
+/// Writes a deleted built-in back at its latest version.
+pub fn restore(
+    templates_dir: &Path,
+    builtins: &[BuiltinTemplate],
+    name: &str,
+) -> Result<(), String> {
+    let builtin = builtins
+        .iter()
+        .find(|builtin| builtin.name == name)
+        .ok_or_else(|| format!("No built-in template named '{}'", name))?;
+    if templates::template_path(templates_dir, None, name)?.exists() {
+        return Err(format!("Template '{}' already exists", name));
+    }
+    let mut state = read_state(templates_dir)?.unwrap_or_default();
+    install(templates_dir, &mut state, builtin)?;
+    write_state(templates_dir, &state)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const FIRST: [BuiltinTemplate; 2] = [
+        BuiltinTemplate {
+            name: "Web",
+            version: 1,
+            content: "web\n",
+        },
+        BuiltinTemplate {
+            name: "Cli",
+            version: 1,
+            content: "cli\n",
+        },
+    ];
+    const SECOND: [BuiltinTemplate; 2] = [
+        BuiltinTemplate {
+            name: "Web",
+            version: 2,
+            content: "web\n\tpublic\n",
+        },
+        BuiltinTemplate {
+            name: "Cli",
+            version: 2,
+            content: "cli\n\tsrc\n",
+        },
+    ];
+
+    fn statuses(root: &Path, builtins: &[BuiltinTemplate]) -> Vec<(String, BuiltinStatus)> {
+        list(root, builtins)
+            .unwrap()
+            .into_iter()
+            .map(|info| (info.name, info.status))
+            .collect()
+    }
+
+    fn read(root: &Path, name: &str) -> String {
+        fs::read_to_string(templates::template_path(root, None, name).unwrap()).unwrap()
+    }
+
+    #[test]
+    fn builtins_are_seeded_once() {
+        let dir = tempfile::tempdir().unwrap();
+        let root = dir.path();
+        sync(root, &FIRST).unwrap();
+        assert_eq!(read(root, "Web"), "web\n");
+        assert_eq!(
+            statuses(root, &FIRST),
+            [
+                ("Web".to_string(), BuiltinStatus::UpToDate),
+                ("Cli".to_string(), BuiltinStatus::UpToDate),
+            ]
+        );
+
+        fs::remove_file(templates::template_path(root, None, "Cli").unwrap()).unwrap();
+        sync(root, &FIRST).unwrap();
+        assert_eq!(statuses(root, &FIRST)[1].1, BuiltinStatus::Deleted);
+        restore(root, &FIRST, "Cli").unwrap();
+        assert_eq!(read(root, "Cli"), "cli\n");
+    }
+
+    #[test]
+    fn only_unmodified_builtins_are_updated() {
+        let dir = tempfile::tempdir().unwrap();
+        let root = dir.path();
+        sync(root, &FIRST).unwrap();
+        templates::save_template(root, None, "Cli", "my cli\n").unwrap();
+
+        sync(root, &SECOND).unwrap();
+        assert_eq!(
+            statuses(root, &SECOND),
+            [
+                ("Web".to_string(), BuiltinStatus::UpdateAvailable),
+                ("Cli".to_string(), BuiltinStatus::Modified),
+            ]
+        );
+        assert_eq!(apply_updates(root, &SECOND, None).unwrap(), ["Web"]);
+        assert_eq!(read(root, "Web"), "web\n\tpublic\n");
+        assert_eq!(read(root, "Cli"), "my cli\n");
+        assert_eq!(statuses(root, &SECOND)[0].1, BuiltinStatus::UpToDate);
+    }
+
+    #[test]
+    fn legacy_seeded_files_can_be_updated() {
+        let dir = tempfile::tempdir().unwrap();
+        let root = dir.path();
+        fs::write(root.join(LEGACY_SENTINEL_FILE), "").unwrap();
+        templates::save_template(root, None, "Web", "old web\n").unwrap();
+
+        sync(root, &SECOND).unwrap();
+        assert!(!root.join(LEGACY_SENTINEL_FILE).exists());
+        assert_eq!(
+            statuses(root, &SECOND),
+            [
+                ("Web".to_string(), BuiltinStatus::UpdateAvailable),
+                ("Cli".to_string(), BuiltinStatus::Deleted),
+            ]
+        );
+        assert_eq!(apply_updates(root, &SECOND, None).unwrap(), ["Web"]);
+        assert_eq!(read(root, "Web"), "web\n\tpublic\n");
+    }
+
+    #[test]
+    fn user_templates_with_a_builtin_name_are_left_alone() {
+        let dir = tempfile::tempdir().unwrap();
+        let root = dir.path();
+        templates::save_template(root, None, "Web", "mine\n").unwrap();
+
+        sync(root, &FIRST).unwrap();
+        assert_eq!(read(root, "Web"), "mine\n");
+        assert_eq!(statuses(root, &FIRST)[0].1, BuiltinStatus::Modified);
+        assert_eq!(apply_updates(root, &SECOND, None).unwrap(), ["Cli"]);
+        assert_eq!(read(root, "Web"), "mine\n");
+    }
+}
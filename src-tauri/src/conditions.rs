@@ -0,0 +1,358 @@
+// Conditional sections: lines between `@if <condition>` and `@endif` (with an
+// optional `@else`) are kept only when the condition holds.
+//
+//   project
+//   	src
+//   	@if with_tests
+//   	tests
+//   	@endif
+//   	@if os == windows
+//   	setup.bat
+//   	@else
+//   	setup.sh
+//   	@endif
+//
+// Conditions are a variable name (set and not `false`, `no`, `0` or empty),
+// `!name`, `name == value` or `name != value`, joined with `&&` and `||`.
+// `os` is `macos`, `windows` or `linux` unless a variable of that name exists.
+
+use crate::structure;
+
+const IF_DIRECTIVE: &str = "@if";
+const ELSE_DIRECTIVE: &str = "@else";
+const END_DIRECTIVE: &str = "@endif";
+const OS_VARIABLE: &str = "os";
+/// Values that leave a flag unset, compared case-insensitively.
+const FALSE_VALUES: [&str; 4] = ["", "false", "no", "0"];
+/// Values a boolean variable may take besides the `FALSE_VALUES`.
+const TRUE_VALUES: [&str; 3] = ["true", "yes", "1"];
+
+pub(crate) enum Directive<'a> {
+    If(&'a str),
+    Else,
+    End,
+}
+
+struct Section {
+    /// Whether the enclosing sections are kept.
+    parent_kept: bool,
+    condition: bool,
+    in_else: bool,
+    line: usize,
+}
+
+impl Section {
+    fn kept(&self) -> bool {
+        self.parent_kept && self.condition != self.in_else
+    }
+}
+
+pub(crate) fn directive(line: &str) -> Option<Directive<'_>> {
+    let entry = line.trim_matches(|c: char| c == '\t' || c == '\r' || c == '\n' || c == ' ');
+    let (word, rest) = entry.split_once(char::is_whitespace).unwrap_or((entry, ""));
+    match word {
+        IF_DIRECTIVE => Some(Directive::If(rest.trim())),
+        ELSE_DIRECTIVE if rest.trim().is_empty() => Some(Directive::Else),
+        END_DIRECTIVE if rest.trim().is_empty() => Some(Directive::End),
+        _ => None,
+    }
+}
+
+/// Drops the sections of `body` whose condition does not hold, along with
+/// every directive line. `first_line` is the line number of the body in the
+/// whole text, for error messages.
+pub fn apply(
+    body: &str,
+    first_line: usize,
+    value_of: &dyn Fn(&str) -> Option<String>,
+) -> Result<String, String> {
+    if !body.contains('@') {
+        return Ok(body.to_string());
+    }
+    let mut output = String::with_capacity(body.len());
+    let mut sections: Vec<Section> = Vec::new();
+    let mut open_block: Option<String> = None;
+
+    for (index, line) in body.split_inclusive('\n').enumerate() {
+        let line_number = first_line + index;
+        let kept = sections.last().map_or(true, Section::kept);
+        let text = line.trim_end_matches(['\n', '\r']);
+
+        // Content blocks are copied as they are, directives included.
+        if let Some(delimiter) = &open_block {
+            if text.trim_start_matches('\t') == delimiter {
+                open_block = None;
+            }
+            if kept {
+                output.push_str(line);
+            }
+            continue;
+        }
+
+        match directive(text) {
+            Some(Directive::If(condition)) => {
+                let holds = evaluate(condition, value_of)
+                    .map_err(|error| format!("Line {}: {}", line_number, error))?;
+                sections.push(Section {
+                    parent_kept: kept,
+                    condition: holds,
+                    in_else: false,
+                    line: line_number,
+                });
+            }
+            Some(Directive::Else) => match sections.last_mut() {
+                Some(section) if !section.in_else => section.in_else = true,
+                Some(_) => return Err(format!("Line {}: duplicate '@else'", line_number)),
+                None => return Err(format!("Line {}: '@else' without '@if'", line_number)),
+            },
+            Some(Directive::End) => {
+                if sections.pop().is_none() {
+                    return Err(format!("Line {}: '@endif' without '@if'", line_number));
+                }
+            }
+            None => {
+                open_block = structure::content_delimiter(text).map(str::to_string);
+                if kept {
+                    output.push_str(line);
+                }
+            }
+        }
+    }
+
+    match sections.last() {
+        Some(section) => Err(format!(
+            "Line {}: '@if' is not closed with '@endif'",
+            section.line
+        )),
+        None => Ok(output),
+    }
+}
+
+/// Checks the syntax of an `@if` condition without evaluating it.
+pub(crate) fn check_condition(condition: &str) -> Result<(), String> {
+    evaluate(condition, &|_| None).map(|_| ())
+}
+
+fn evaluate(condition: &str, value_of: &dyn Fn(&str) -> Option<String>) -> Result<bool, String> {
+    if condition.is_empty() {
+        return Err("'@if' needs a condition".to_string());
+    }
+    let value = |name: &str| {
+        value_of(name).or_else(|| (name == OS_VARIABLE).then(|| std::env::consts::OS.to_string()))
+    };
+    let mut any = false;
+    for alternative in condition.split("||") {
+        let mut all = true;
+        for term in alternative.split("&&") {
+            all &= evaluate_term(term.trim(), &value)?;
+        }
+        any |= all;
+    }
+    Ok(any)
+}
+
+fn evaluate_term(term: &str, value: &dyn Fn(&str) -> Option<String>) -> Result<bool, String> {
+    if let Some((name, expected)) = term.split_once("!=") {
+        return Ok(!compare(name, expected, value));
+    }
+    if let Some((name, expected)) = term.split_once("==") {
+        return Ok(compare(name, expected, value));
+    }
+    let (negated, name) = match term.strip_prefix('!') {
+        Some(name) => (true, name.trim()),
+        None => (false, term),
+    };
+    if name.is_empty() || name.contains(char::is_whitespace) {
+        return Err(format!("Invalid condition: '{}'", term));
+    }
+    let set = value(name).is_some_and(|value| is_set(&value));
+    Ok(set != negated)
+}
+
+/// Whether a flag with this value counts as set.
+fn is_set(value: &str) -> bool {
+    !FALSE_VALUES.contains(&value.trim().to_lowercase().as_str())
+}
+
+/// Whether `value` is one a boolean variable accepts: `true`, `yes`, `1` or
+/// any of the values that leave a flag unset.
+pub(crate) fn is_flag_value(value: &str) -> bool {
+    let value = value.trim().to_lowercase();
+    FALSE_VALUES.contains(&value.as_str()) || TRUE_VALUES.contains(&value.as_str())
+}
+
+/// Compares a variable with a literal, which may be quoted. Unset variables
+/// compare as empty text.
+fn compare(name: &str, expected: &str, value: &dyn Fn(&str) -> Option<String>) -> bool {
+    let expected = expected.trim();
+    let expected = expected
+        .strip_prefix('"')
+        .and_then(|rest| rest.strip_suffix('"'))
+        .unwrap_or(expected);
+    let actual = value(name.trim()).unwrap_or_default();
+    actual.eq_ignore_ascii_case(expected)
+}
+
+/// Variables named in the conditions of `body`, each with whether it is only
+/// ever tested as a flag. The built-in `os` is left out.
+pub fn condition_names(body: &str) -> Vec<(String, bool)> {
+    let mut names: Vec<(String, bool)> = Vec::new();
+    let mut open_block: Option<&str> = None;
+    for line in body.lines() {
+        if let Some(delimiter) = open_block {
+            if line.trim_start_matches('\t').trim_end_matches('\r') == delimiter {
+                open_block = None;
+            }
+            continue;
+        }
+        let Some(Directive::If(condition)) = directive(line) else {
+            open_block = structure::content_delimiter(line.trim_end_matches('\r'));
+            continue;
+        };
+        for term in condition.split(['|', '&']).map(str::trim) {
+            let (name, is_flag) = match term.split_once(['=', '!']) {
+                Some((name, _)) if !name.trim().is_empty() => (name.trim(), false),
+                _ => (term.trim_start_matches('!').trim(), true),
+            };
+            if name.is_empty() || name == OS_VARIABLE {
+                continue;
+            }
+            match names.iter_mut().find(|(known, _)| known == name) {
+                Some((_, flag)) => *flag &= is_flag,
+                None => names.push((name.to_string(), is_flag)),
+            }
+        }
+    }
+    names
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+
+    fn values(pairs: &[(&str, &str)]) -> HashMap<String, String> {
+        pairs
+            .iter()
+            .map(|(name, value)| (name.to_string(), value.to_string()))
+            .collect()
+    }
+
+    fn holds(condition: &str, pairs: &[(&str, &str)]) -> bool {
+        let values = values(pairs);
+        evaluate(condition, &|name| values.get(name).cloned()).unwrap()
+    }
+
+    fn apply_with(body: &str, pairs: &[(&str, &str)]) -> Result<String, String> {
+        let values = values(pairs);
+        apply(body, 1, &|name| values.get(name).cloned())
+    }
+
+    #[test]
+    fn flags_are_set_unless_empty_or_false() {
+        assert!(holds("tests", &[("tests", "yes")]));
+        assert!(holds("tests", &[("tests", "anything")]));
+        for value in ["", "false", "No", "0"] {
+            assert!(!holds("tests", &[("tests", value)]));
+            assert!(holds("!tests", &[("tests", value)]));
+        }
+        assert!(!holds("tests", &[]));
+        assert!(holds("! tests", &[]));
+    }
+
+    #[test]
+    fn and_binds_tighter_than_or() {
+        let pairs = [("a", "1"), ("b", "0"), ("c", "1")];
+        assert!(!holds("a && b", &pairs));
+        assert!(holds("a || b", &pairs));
+        assert!(holds("b && a || c", &pairs));
+        assert!(!holds("b && a || b && c", &pairs));
+        assert!(holds("a && c && !b", &pairs));
+    }
+
+    #[test]
+    fn comparisons_accept_quoted_literals() {
+        let pairs = [("license", "MIT"), ("title", "My App")];
+        assert!(holds("license == mit", &pairs));
+        assert!(holds("license == \"MIT\"", &pairs));
+        assert!(holds("title == \"My App\"", &pairs));
+        assert!(holds("license != \"Apache\"", &pairs));
+        assert!(!holds("license != MIT", &pairs));
+        assert!(holds("missing == \"\"", &pairs));
+        assert!(holds("license == MIT && title != \"\"", &pairs));
+    }
+
+    #[test]
+    fn os_defaults_to_the_current_platform() {
+        assert!(holds(&format!("os == {}", std::env::consts::OS), &[]));
+        assert!(holds("os == plan9", &[("os", "plan9")]));
+    }
+
+    #[test]
+    fn invalid_conditions_are_rejected() {
+        assert!(check_condition("").is_err());
+        assert!(check_condition("two words").is_err());
+        assert!(check_condition("a && ").is_err());
+        assert!(check_condition("a && !b || c == d").is_ok());
+    }
+
+    #[test]
+    fn nested_sections_are_kept_only_inside_kept_parents() {
+        let body = "root\n@if web\n\tpublic\n\t@if tests\n\te2e\n\t@else\n\tno-tests\n\t@endif\n@else\n\tcli\n@endif\n";
+        assert_eq!(
+            apply_with(body, &[("web", "yes"), ("tests", "yes")]).unwrap(),
+            "root\n\tpublic\n\te2e\n"
+        );
+        assert_eq!(
+            apply_with(body, &[("web", "yes")]).unwrap(),
+            "root\n\tpublic\n\tno-tests\n"
+        );
+        assert_eq!(
+            apply_with(body, &[("tests", "yes")]).unwrap(),
+            "root\n\tcli\n"
+        );
+    }
+
+    #[test]
+    fn unbalanced_directives_are_reported_with_their_line() {
+        assert_eq!(
+            apply_with("a\n@if x\nb\n", &[]).unwrap_err(),
+            "Line 2: '@if' is not closed with '@endif'"
+        );
+        assert_eq!(
+            apply_with("a\n@endif\n", &[]).unwrap_err(),
+            "Line 2: '@endif' without '@if'"
+        );
+        assert_eq!(
+            apply_with("@if x\n@else\n@else\n@endif\n", &[]).unwrap_err(),
+            "Line 3: duplicate '@else'"
+        );
+    }
+
+    #[test]
+    fn directives_inside_content_blocks_are_content() {
+        let body = "@if docs\nREADME.md <<EOF\n@if draft\n@endif\nEOF\n@endif\nMakefile <<END\n@else\nEND\n";
+        assert_eq!(
+            apply_with(body, &[("docs", "1")]).unwrap(),
+            "README.md <<EOF\n@if draft\n@endif\nEOF\nMakefile <<END\n@else\nEND\n"
+        );
+        assert_eq!(
+            apply_with(body, &[]).unwrap(),
+            "Makefile <<END\n@else\nEND\n"
+        );
+        assert_eq!(condition_names(body), [("docs".to_string(), true)]);
+    }
+
+    #[test]
+    fn condition_names_tell_flags_from_compared_variables() {
+        let body = "@if tests && !ci\n@endif\n@if license == MIT || os != linux\n@endif\n@if ci == github\n@endif\n";
+        assert_eq!(
+            condition_names(body),
+            [
+                ("tests".to_string(), true),
+                ("ci".to_string(), false),
+                ("license".to_string(), false),
+            ]
+        );
+    }
+}
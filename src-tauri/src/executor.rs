@@ -0,0 +1,837 @@
+// Executes structure operations (create, copy, move, link) against the filesystem in
+// a single backend call, optionally rolling everything back on failure.
+
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use crate::journal;
+use crate::structure::{self, ConflictPolicy, NodeKind, StructureDocument, StructureNode};
+
+// -----------------
+// Types
+// -----------------
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum OperationType {
+    Create,
+    Copy,
+    Move,
+    Symlink,
+    HardLink,
+}
+
+impl OperationType {
+    fn label(self) -> &'static str {
+        match self {
+            OperationType::Create => "create",
+            OperationType::Copy => "copy",
+            OperationType::Move => "move",
+            OperationType::Symlink => "symlink",
+            OperationType::HardLink => "hard link",
+        }
+    }
+}
+
+/// One filesystem operation, in the same shape the frontend gets from
+/// `@filearchitect/core`'s `getStructure`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Operation {
+    #[serde(rename = "type")]
+    pub operation_type: OperationType,
+    pub target_path: String,
+    /// Copy/move source, or what a link points to. Symbolic link targets are
+    /// kept relative when written relative.
+    pub source_path: Option<String>,
+    pub is_directory: bool,
+    /// Overrides the run's conflict policy for this operation.
+    #[serde(default)]
+    pub conflict_policy: Option<ConflictPolicy>,
+    /// Exact content for a created file, from a `<<EOF` block.
+    #[serde(default)]
+    pub content: Option<String>,
+}
+
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct ExecuteOptions {
+    /// Roll back every change made by the run as soon as one operation fails.
+    pub all_or_nothing: bool,
+    /// What to do with targets that already exist, unless a line says otherwise.
+    pub conflict_policy: ConflictPolicy,
+    /// Fill new files from the local blank files cache when one matches.
+    pub functional_blank_files: bool,
+    /// Directory holding the cached `blank.<ext>` files.
+    #[serde(skip)]
+    pub blank_files_dir: Option<PathBuf>,
+    /// Where to write the undo journal for the run, if anywhere.
+    #[serde(skip)]
+    pub history_dir: Option<PathBuf>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct StructureCreationSummary {
+    pub total_operations: usize,
+    pub create_file_count: usize,
+    pub create_directory_count: usize,
+    pub copy_count: usize,
+    pub move_count: usize,
+    pub link_count: usize,
+    pub existing_target_count: usize,
+    pub existing_targets: Vec<String>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct FailedOperation {
+    #[serde(rename = "type")]
+    pub operation_type: String,
+    pub target_path: String,
+    pub source_path: Option<String>,
+    pub is_directory: bool,
+    pub message: String,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub enum OperationStatus {
+    Completed,
+    Skipped,
+    Failed,
+    RolledBack,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct OperationOutcome {
+    #[serde(rename = "type")]
+    pub operation_type: String,
+    pub target_path: String,
+    /// Where the entry actually ended up after renames.
+    pub final_path: String,
+    pub status: OperationStatus,
+    /// The policy applied because the target already existed, if it did.
+    pub conflict_policy: Option<ConflictPolicy>,
+}
+
+/// Mirrors the frontend's `CreateFoldersExecutionResult`.
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ExecutionResult {
+    pub base_dir: String,
+    pub summary: StructureCreationSummary,
+    pub completed_count: usize,
+    pub skipped_count: usize,
+    pub failure_count: usize,
+    pub failures: Vec<FailedOperation>,
+    pub outcomes: Vec<OperationOutcome>,
+    pub partial_success: bool,
+    pub rolled_back: bool,
+    /// Journal id that can be passed to `revert_creation_run`.
+    pub run_id: Option<String>,
+    /// Problems that did not fail an operation, such as an undo journal that
+    /// could not be written.
+    pub warnings: Vec<String>,
+}
+
+/// A change made on disk, recorded so it can be undone.
+#[derive(Debug, Clone)]
+pub(crate) enum AppliedChange {
+    CreatedFile(PathBuf),
+    CreatedDirectory(PathBuf),
+    /// A symbolic link; hard links are recorded as created files.
+    CreatedSymlink(PathBuf),
+    Moved {
+        from: PathBuf,
+        to: PathBuf,
+    },
+    /// An existing file set aside before being overwritten.
+    Replaced {
+        target: PathBuf,
+        backup: PathBuf,
+    },
+}
+
+// -----------------
+// Planning
+// -----------------
+
+/// Flattens a parsed structure into operations rooted at `base_dir`, expanding
+/// repeaters. Relative copy/move sources are resolved against `base_dir`, and
+/// relative link targets against the folder holding the link.
+pub fn operations_from_document(document: &StructureDocument, base_dir: &Path) -> Vec<Operation> {
+    let mut operations = Vec::new();
+    collect_operations(&document.nodes, base_dir, base_dir, &mut operations);
+    operations
+}
+
+fn collect_operations(
+    nodes: &[StructureNode],
+    parent: &Path,
+    base_dir: &Path,
+    operations: &mut Vec<Operation>,
+) {
+    for node in nodes {
+        let names: Vec<String> = match node.repeat {
+            Some(count) => (1..=count)
+                .map(|index| structure::repeated_name(&node.name, index))
+                .collect(),
+            None => vec![node.name.clone()],
+        };
+
+        for name in names {
+            let target = parent.join(&name);
+            let source = node.source.as_deref().map(|source| match node.kind {
+                NodeKind::Symlink => crate::expand_tilde_path(source),
+                NodeKind::HardLink => resolve_source(source, parent),
+                _ => resolve_source(source, base_dir),
+            });
+            let (operation_type, is_directory) = match node.kind {
+                NodeKind::Folder => (OperationType::Create, true),
+                NodeKind::File => (OperationType::Create, false),
+                NodeKind::Copy => (
+                    OperationType::Copy,
+                    source.as_deref().is_some_and(Path::is_dir),
+                ),
+                NodeKind::Move => (
+                    OperationType::Move,
+                    source.as_deref().is_some_and(Path::is_dir),
+                ),
+                NodeKind::Symlink => (
+                    OperationType::Symlink,
+                    source
+                        .as_deref()
+                        .is_some_and(|source| link_destination(&target, source).is_dir()),
+                ),
+                NodeKind::HardLink => (OperationType::HardLink, false),
+            };
+            operations.push(Operation {
+                operation_type,
+                target_path: target.to_string_lossy().into_owned(),
+                source_path: source.map(|source| source.to_string_lossy().into_owned()),
+                is_directory,
+                conflict_policy: node.conflict_policy,
+                content: node.content.clone(),
+            });
+            collect_operations(&node.children, &target, base_dir, operations);
+        }
+    }
+}
+
+fn resolve_source(source: &str, base_dir: &Path) -> PathBuf {
+    let expanded = crate::expand_tilde_path(source);
+    if expanded.is_absolute() {
+        expanded
+    } else {
+        base_dir.join(expanded)
+    }
+}
+
+/// Makes sure every target stays inside `base_dir`, so a name such as `..` or
+/// one filled in from a variable cannot create entries elsewhere.
+pub fn check_targets(base_dir: &Path, operations: &[Operation]) -> Result<(), String> {
+    for operation in operations {
+        let inside = Path::new(&operation.target_path)
+            .strip_prefix(base_dir)
+            .is_ok_and(|relative| {
+                relative
+                    .components()
+                    .all(|component| matches!(component, std::path::Component::Normal(_)))
+            });
+        if !inside {
+            return Err(format!(
+                "Target is outside the destination folder: {}",
+                operation.target_path
+            ));
+        }
+    }
+    Ok(())
+}
+
+/// Where a symbolic link at `link` pointing at `source` ends up.
+pub(crate) fn link_destination(link: &Path, source: &Path) -> PathBuf {
+    match link.parent() {
+        Some(parent) if source.is_relative() => parent.join(source),
+        _ => source.to_path_buf(),
+    }
+}
+
+pub fn summarize(
+    operations: &[Operation],
+    existing_targets: Vec<String>,
+) -> StructureCreationSummary {
+    let count = |operation_type: OperationType, is_directory: Option<bool>| {
+        operations
+            .iter()
+            .filter(|op| op.operation_type == operation_type)
+            .filter(|op| is_directory.map_or(true, |dir| op.is_directory == dir))
+            .count()
+    };
+    StructureCreationSummary {
+        total_operations: operations.len(),
+        create_file_count: count(OperationType::Create, Some(false)),
+        create_directory_count: count(OperationType::Create, Some(true)),
+        copy_count: count(OperationType::Copy, None),
+        move_count: count(OperationType::Move, None),
+        link_count: count(OperationType::Symlink, None) + count(OperationType::HardLink, None),
+        existing_target_count: existing_targets.len(),
+        existing_targets,
+    }
+}
+
+// -----------------
+// Execution
+// -----------------
+pub fn execute(
+    base_dir: &str,
+    operations: &[Operation],
+    options: &ExecuteOptions,
+) -> ExecutionResult {
+    let base_dir = crate::expand_tilde_path(base_dir)
+        .to_string_lossy()
+        .into_owned();
+    let existing_targets = operations
+        .iter()
+        .filter(|op| fs::symlink_metadata(&op.target_path).is_ok())
+        .map(|op| op.target_path.clone())
+        .collect();
+    let summary = summarize(operations, existing_targets);
+    let mut applied: Vec<AppliedChange> = Vec::new();
+    let mut failures: Vec<FailedOperation> = Vec::new();
+    let mut outcomes: Vec<OperationOutcome> = Vec::with_capacity(operations.len());
+    // Folders created under a new name; later targets inside them follow along.
+    let mut redirects: Vec<(PathBuf, PathBuf)> = Vec::new();
+    let mut completed_count = 0;
+    let mut skipped_count = 0;
+    let mut rolled_back = false;
+    let mut warnings = Vec::new();
+
+    for operation in operations {
+        let mut target = redirected(Path::new(&operation.target_path), &redirects);
+        let policy = operation.conflict_policy.unwrap_or(options.conflict_policy);
+        let conflict_policy = fs::symlink_metadata(&target).is_ok().then_some(policy);
+
+        let result = match conflict_policy {
+            Some(ConflictPolicy::Skip) => Ok(OperationStatus::Skipped),
+            Some(ConflictPolicy::Abort) => Err(io::Error::new(
+                io::ErrorKind::AlreadyExists,
+                format!("Target already exists: {}", target.display()),
+            )),
+            Some(ConflictPolicy::Rename) => {
+                let renamed = unique_path(&target, operation.is_directory);
+                redirects.push((target, renamed.clone()));
+                target = renamed;
+                apply_operation(operation, &target, options, &mut applied)
+                    .map(|()| OperationStatus::Completed)
+            }
+            Some(ConflictPolicy::Overwrite) | None => {
+                apply_operation(operation, &target, options, &mut applied)
+                    .map(|()| OperationStatus::Completed)
+            }
+        };
+
+        let status = match result {
+            Ok(status) => status,
+            Err(error) => {
+                failures.push(FailedOperation {
+                    operation_type: operation.operation_type.label().to_string(),
+                    target_path: operation.target_path.clone(),
+                    source_path: operation.source_path.clone(),
+                    is_directory: operation.is_directory,
+                    message: error.to_string(),
+                });
+                OperationStatus::Failed
+            }
+        };
+        match status {
+            OperationStatus::Completed => completed_count += 1,
+            OperationStatus::Skipped => skipped_count += 1,
+            _ => {}
+        }
+        outcomes.push(OperationOutcome {
+            operation_type: operation.operation_type.label().to_string(),
+            target_path: operation.target_path.clone(),
+            final_path: target.to_string_lossy().into_owned(),
+            status,
+            conflict_policy,
+        });
+
+        if status == OperationStatus::Failed {
+            if options.all_or_nothing {
+                failures.extend(rollback(&applied));
+                applied.clear();
+                for outcome in outcomes.iter_mut() {
+                    if outcome.status == OperationStatus::Completed {
+                        outcome.status = OperationStatus::RolledBack;
+                    }
+                }
+                completed_count = 0;
+                rolled_back = true;
+                break;
+            }
+            if conflict_policy == Some(ConflictPolicy::Abort) {
+                break;
+            }
+        }
+    }
+
+    let run_id = match options.history_dir.as_deref() {
+        Some(history_dir) if !applied.is_empty() => {
+            match journal::record_run(history_dir, &base_dir, &applied) {
+                Ok(run_id) => Some(run_id),
+                Err(error) => {
+                    warnings.push(format!(
+                        "The run cannot be reverted, its undo journal could not be written: {}",
+                        error
+                    ));
+                    None
+                }
+            }
+        }
+        _ => None,
+    };
+    discard_backups(&applied);
+
+    ExecutionResult {
+        base_dir,
+        summary,
+        completed_count,
+        skipped_count,
+        failure_count: failures.len(),
+        partial_success: completed_count > 0 && !failures.is_empty(),
+        failures,
+        outcomes,
+        rolled_back,
+        run_id,
+        warnings,
+    }
+}
+
+/// Rewrites `path` for every folder that was created under another name.
+fn redirected(path: &Path, redirects: &[(PathBuf, PathBuf)]) -> PathBuf {
+    let mut path = path.to_path_buf();
+    for (from, to) in redirects {
+        if let Ok(rest) = path.strip_prefix(from) {
+            path = to.join(rest);
+        }
+    }
+    path
+}
+
+/// First free sibling name of the form `name (2).ext`, `name (3).ext`, …
+fn unique_path(target: &Path, is_directory: bool) -> PathBuf {
+    let file_name = target
+        .file_name()
+        .map(|name| name.to_string_lossy().into_owned())
+        .unwrap_or_default();
+    let (stem, extension) = match (is_directory, file_name.rfind('.')) {
+        (false, Some(dot)) if dot > 0 => file_name.split_at(dot),
+        _ => (file_name.as_str(), ""),
+    };
+    (2..)
+        .map(|index| target.with_file_name(format!("{} ({}){}", stem, index, extension)))
+        .find(|candidate| fs::symlink_metadata(candidate).is_err())
+        .unwrap_or_else(|| target.to_path_buf())
+}
+
+fn apply_operation(
+    operation: &Operation,
+    target: &Path,
+    options: &ExecuteOptions,
+    applied: &mut Vec<AppliedChange>,
+) -> io::Result<()> {
+    let target = target.to_path_buf();
+    if let Some(parent) = target.parent() {
+        create_dir_recorded(parent, applied)?;
+    }
+
+    match operation.operation_type {
+        OperationType::Create if operation.is_directory => create_dir_recorded(&target, applied),
+        OperationType::Create => {
+            set_aside_existing_file(&target, applied)?;
+            match &operation.content {
+                Some(content) => fs::write(&target, content)?,
+                None => write_blank_file(&target, options)?,
+            }
+            applied.push(AppliedChange::CreatedFile(target));
+            Ok(())
+        }
+        OperationType::Copy => {
+            let source = required_source(operation)?;
+            if source.is_dir() {
+                copy_dir_recorded(&source, &target, applied)
+            } else {
+                copy_file_recorded(&source, &target, applied)
+            }
+        }
+        OperationType::Move => {
+            let source = required_source(operation)?;
+            if !source.exists() {
+                return Err(io::Error::new(
+                    io::ErrorKind::NotFound,
+                    format!("Source does not exist: {}", source.display()),
+                ));
+            }
+            if source.is_dir() && fs::symlink_metadata(&target).is_ok() {
+                return Err(io::Error::new(
+                    io::ErrorKind::AlreadyExists,
+                    format!("Target already exists: {}", target.display()),
+                ));
+            }
+            set_aside_existing_file(&target, applied)?;
+            move_path(&source, &target)?;
+            applied.push(AppliedChange::Moved {
+                from: source,
+                to: target,
+            });
+            Ok(())
+        }
+        OperationType::Symlink => {
+            let source = required_source(operation)?;
+            set_aside_existing_file(&target, applied)?;
+            create_symlink(&source, &target, operation.is_directory)?;
+            applied.push(AppliedChange::CreatedSymlink(target));
+            Ok(())
+        }
+        OperationType::HardLink => {
+            let source = required_source(operation)?;
+            if source.is_dir() {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidInput,
+                    format!("Hard links cannot point to folders: {}", source.display()),
+                ));
+            }
+            set_aside_existing_file(&target, applied)?;
+            fs::hard_link(&source, &target)?;
+            applied.push(AppliedChange::CreatedFile(target));
+            Ok(())
+        }
+    }
+}
+
+#[cfg(unix)]
+pub(crate) fn create_symlink(source: &Path, link: &Path, _is_directory: bool) -> io::Result<()> {
+    std::os::unix::fs::symlink(source, link)
+}
+
+#[cfg(windows)]
+pub(crate) fn create_symlink(source: &Path, link: &Path, is_directory: bool) -> io::Result<()> {
+    if is_directory {
+        std::os::windows::fs::symlink_dir(source, link)
+    } else {
+        std::os::windows::fs::symlink_file(source, link)
+    }
+}
+
+/// Removes a symbolic link itself; Windows folder links need `remove_dir`.
+pub(crate) fn remove_symlink(link: &Path) -> io::Result<()> {
+    fs::remove_file(link).or_else(|_| fs::remove_dir(link))
+}
+
+fn required_source(operation: &Operation) -> io::Result<PathBuf> {
+    operation
+        .source_path
+        .as_ref()
+        .map(PathBuf::from)
+        .ok_or_else(|| {
+            io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!(
+                    "Source path is required for {} operations",
+                    operation.operation_type.label()
+                ),
+            )
+        })
+}
+
+/// Creates `path` and any missing ancestors, recording each directory created.
+fn create_dir_recorded(path: &Path, applied: &mut Vec<AppliedChange>) -> io::Result<()> {
+    let missing: Vec<&Path> = path
+        .ancestors()
+        .take_while(|ancestor| !ancestor.exists())
+        .collect();
+    for directory in missing.into_iter().rev() {
+        fs::create_dir(directory)?;
+        applied.push(AppliedChange::CreatedDirectory(directory.to_path_buf()));
+    }
+    if path.is_dir() {
+        Ok(())
+    } else {
+        Err(io::Error::new(
+            io::ErrorKind::AlreadyExists,
+            format!(
+                "A file already exists where a folder is expected: {}",
+                path.display()
+            ),
+        ))
+    }
+}
+
+/// Renames an existing file or link out of the way so it can be restored on
+/// rollback.
+fn set_aside_existing_file(target: &Path, applied: &mut Vec<AppliedChange>) -> io::Result<()> {
+    match fs::symlink_metadata(target) {
+        Ok(metadata) if !metadata.is_dir() => {}
+        _ => return Ok(()),
+    }
+    let file_name = target
+        .file_name()
+        .map(|name| name.to_string_lossy().into_owned())
+        .unwrap_or_default();
+    let backup =
+        target.with_file_name(format!(".{}.fa-backup-{}", file_name, uuid::Uuid::new_v4()));
+    fs::rename(target, &backup)?;
+    applied.push(AppliedChange::Replaced {
+        target: target.to_path_buf(),
+        backup,
+    });
+    Ok(())
+}
+
+/// The cached blank file a new file at `target` would be filled from, if any.
+pub(crate) fn blank_file_for(target: &Path, options: &ExecuteOptions) -> Option<PathBuf> {
+    if !options.functional_blank_files {
+        return None;
+    }
+    let extension = target.extension()?.to_string_lossy().to_lowercase();
+    let path = options
+        .blank_files_dir
+        .as_deref()?
+        .join(format!("blank.{}", extension));
+    path.is_file().then_some(path)
+}
+
+fn write_blank_file(target: &Path, options: &ExecuteOptions) -> io::Result<()> {
+    match blank_file_for(target, options) {
+        Some(blank) => fs::copy(blank, target).map(|_| ()),
+        None => fs::write(target, ""),
+    }
+}
+
+fn copy_file_recorded(
+    source: &Path,
+    target: &Path,
+    applied: &mut Vec<AppliedChange>,
+) -> io::Result<()> {
+    set_aside_existing_file(target, applied)?;
+    fs::copy(source, target)?;
+    applied.push(AppliedChange::CreatedFile(target.to_path_buf()));
+    Ok(())
+}
+
+fn copy_dir_recorded(
+    source: &Path,
+    target: &Path,
+    applied: &mut Vec<AppliedChange>,
+) -> io::Result<()> {
+    create_dir_recorded(target, applied)?;
+    for entry in fs::read_dir(source)? {
+        let entry = entry?;
+        let destination = target.join(entry.file_name());
+        if entry.file_type()?.is_dir() {
+            copy_dir_recorded(&entry.path(), &destination, applied)?;
+        } else {
+            copy_file_recorded(&entry.path(), &destination, applied)?;
+        }
+    }
+    Ok(())
+}
+
+pub(crate) fn copy_dir_recursive(source: &Path, target: &Path) -> io::Result<()> {
+    fs::create_dir_all(target)?;
+    for entry in fs::read_dir(source)? {
+        let entry = entry?;
+        let destination = target.join(entry.file_name());
+        if entry.file_type()?.is_dir() {
+            copy_dir_recursive(&entry.path(), &destination)?;
+        } else {
+            fs::copy(entry.path(), &destination)?;
+        }
+    }
+    Ok(())
+}
+
+/// Renames `source` to `target`, falling back to copy + delete across volumes.
+/// A copy that fails is removed again so nothing is left behind at `target`.
+pub(crate) fn move_path(source: &Path, target: &Path) -> io::Result<()> {
+    if fs::rename(source, target).is_ok() {
+        return Ok(());
+    }
+    if source.is_dir() {
+        if let Err(error) = copy_dir_recursive(source, target) {
+            let _ = fs::remove_dir_all(target);
+            return Err(error);
+        }
+        // Once the copy is complete it is kept, even if the source can only be
+        // partly removed, so no data is lost.
+        fs::remove_dir_all(source)
+    } else {
+        let result = fs::copy(source, target).and_then(|_| fs::remove_file(source));
+        if result.is_err() {
+            let _ = fs::remove_file(target);
+        }
+        result
+    }
+}
+
+/// Undoes `applied` in reverse order, returning a failure for each step that
+/// could not be undone.
+fn rollback(applied: &[AppliedChange]) -> Vec<FailedOperation> {
+    let mut failures = Vec::new();
+    for change in applied.iter().rev() {
+        let (path, result) = match change {
+            AppliedChange::CreatedFile(path) => (path, fs::remove_file(path)),
+            AppliedChange::CreatedDirectory(path) => (path, fs::remove_dir(path)),
+            AppliedChange::CreatedSymlink(path) => (path, remove_symlink(path)),
+            AppliedChange::Moved { from, to } => (to, move_path(to, from)),
+            AppliedChange::Replaced { target, backup } => (target, fs::rename(backup, target)),
+        };
+        if let Err(error) = result {
+            failures.push(FailedOperation {
+                operation_type: "rollback".to_string(),
+                target_path: path.to_string_lossy().into_owned(),
+                source_path: None,
+                is_directory: path.is_dir(),
+                message: error.to_string(),
+            });
+        }
+    }
+    failures
+}
+
+/// Deletes the backups of overwritten files that were not kept by a journal.
+fn discard_backups(applied: &[AppliedChange]) {
+    for change in applied {
+        if let AppliedChange::Replaced { backup, .. } = change {
+            let _ = fs::remove_file(backup);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn run(base: &Path, text: &str, options: &ExecuteOptions) -> ExecutionResult {
+        let document = structure::parse(text).unwrap();
+        let operations = operations_from_document(&document, base);
+        execute(&base.to_string_lossy(), &operations, options)
+    }
+
+    #[test]
+    fn all_or_nothing_rolls_back_every_change() {
+        let dir = tempfile::tempdir().unwrap();
+        let base = dir.path();
+        fs::write(base.join("notes.md"), "keep me").unwrap();
+        fs::write(base.join("README.md"), "original").unwrap();
+        let options = ExecuteOptions {
+            all_or_nothing: true,
+            ..ExecuteOptions::default()
+        };
+
+        let result = run(
+            base,
+            "project\n\tsrc\n\t\tmain.rs\n\t(notes.md)\nREADME.md <<EOF\n\tnew\nEOF\n[missing.txt]",
+            &options,
+        );
+
+        assert!(result.rolled_back);
+        assert_eq!(result.completed_count, 0);
+        assert_eq!(result.failure_count, 1);
+        assert!(result
+            .outcomes
+            .iter()
+            .take(5)
+            .all(|outcome| outcome.status == OperationStatus::RolledBack));
+        assert_eq!(result.outcomes[5].status, OperationStatus::Failed);
+        assert!(!base.join("project").exists());
+        assert_eq!(
+            fs::read_to_string(base.join("notes.md")).unwrap(),
+            "keep me"
+        );
+        assert_eq!(
+            fs::read_to_string(base.join("README.md")).unwrap(),
+            "original"
+        );
+        let mut left: Vec<_> = fs::read_dir(base)
+            .unwrap()
+            .map(|entry| entry.unwrap().file_name())
+            .collect();
+        left.sort();
+        assert_eq!(left, ["README.md", "notes.md"]);
+    }
+
+    #[test]
+    fn failures_are_kept_without_all_or_nothing() {
+        let dir = tempfile::tempdir().unwrap();
+        let base = dir.path();
+
+        let result = run(
+            base,
+            "[missing.txt]\nsrc\n\tmain.rs",
+            &ExecuteOptions::default(),
+        );
+
+        assert!(!result.rolled_back);
+        assert!(result.partial_success);
+        assert_eq!(result.completed_count, 2);
+        assert!(base.join("src").join("main.rs").is_file());
+    }
+
+    #[test]
+    fn targets_must_stay_inside_the_base_folder() {
+        let base = Path::new("/projects/app");
+        let check = |text: &str| {
+            let document = structure::parse(text).unwrap();
+            check_targets(base, &operations_from_document(&document, base))
+        };
+
+        assert!(check("src\n\tmain.rs\n[/tmp/logo.png] > logo.png").is_ok());
+        assert_eq!(
+            check("src\n\t../../etc.conf").unwrap_err(),
+            "Target is outside the destination folder: /projects/app/src/../../etc.conf"
+        );
+        assert!(check("..").is_err());
+    }
+
+    #[test]
+    fn existing_targets_include_dangling_links() {
+        let dir = tempfile::tempdir().unwrap();
+        let base = dir.path();
+        create_symlink(&base.join("gone"), &base.join("link"), false).unwrap();
+
+        let result = run(base, "link !skip", &ExecuteOptions::default());
+
+        assert_eq!(result.summary.existing_target_count, 1);
+        assert_eq!(result.outcomes[0].status, OperationStatus::Skipped);
+    }
+
+    #[test]
+    fn unwritable_journal_is_reported_and_leaves_no_backups() {
+        let dir = tempfile::tempdir().unwrap();
+        let base = dir.path().join("base");
+        fs::create_dir(&base).unwrap();
+        fs::write(base.join("README.md"), "original").unwrap();
+        // A file where the history folder should be.
+        let history = dir.path().join("History");
+        fs::write(&history, "").unwrap();
+        let options = ExecuteOptions {
+            history_dir: Some(history),
+            ..ExecuteOptions::default()
+        };
+
+        let result = run(&base, "README.md <<EOF\n\tnew\nEOF", &options);
+
+        assert_eq!(result.completed_count, 1);
+        assert_eq!(result.run_id, None);
+        assert_eq!(result.warnings.len(), 1);
+        let left: Vec<_> = fs::read_dir(&base)
+            .unwrap()
+            .map(|entry| entry.unwrap().file_name())
+            .collect();
+        assert_eq!(left, ["README.md"]);
+    }
+}
@@ -0,0 +1,605 @@
+// ZIP extraction for downloaded blank-file packages.
+//
+// Archives come from the network, so entries are never trusted: names that
+// would land outside the destination (`../`, absolute paths, or through a
+// symbolic link) are skipped and reported, symbolic links may only point
+// inside the destination, and the number of entries and the total
+// uncompressed size are capped against zip bombs.
+//
+// Extraction reports its progress as it goes and can be cancelled by id from
+// another command while it runs.
+
+use serde::Serialize;
+use std::collections::HashMap;
+use std::fs;
+use std::io::{Read, Write};
+use std::path::{Component, Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use zip::read::ZipFile;
+use zip::ZipArchive;
+
+use crate::executor::create_symlink;
+
+const LIMITS: Limits = Limits {
+    max_entries: 10_000,
+    max_total_size: 1024 * 1024 * 1024,
+};
+/// Link targets are stored as the entry's content; real ones are short.
+const MAX_LINK_TARGET_SIZE: u64 = 4096;
+const SYMLINK_MODE: u32 = 0o120000;
+const FILE_TYPE_MASK: u32 = 0o170000;
+const COPY_CHUNK_SIZE: usize = 64 * 1024;
+const PROGRESS_INTERVAL: Duration = Duration::from_millis(100);
+const CANCELLED: &str = "Extraction cancelled";
+
+pub const EXTRACT_PROGRESS_EVENT: &str = "extract-progress";
+
+// -----------------
+// Types
+// -----------------
+/// Caps against zip bombs.
+struct Limits {
+    max_entries: usize,
+    /// Uncompressed bytes, both as declared and as actually written.
+    max_total_size: u64,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SkippedEntry {
+    pub name: String,
+    pub reason: String,
+}
+
+#[derive(Debug, Clone, Default, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ExtractSummary {
+    pub file_count: usize,
+    pub directory_count: usize,
+    pub link_count: usize,
+    pub skipped: Vec<SkippedEntry>,
+}
+
+/// Sent as `extract-progress` while an archive is extracted.
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ExtractProgress {
+    pub extraction_id: String,
+    pub entries_done: usize,
+    pub entry_count: usize,
+    pub bytes_written: u64,
+    /// Uncompressed size declared by the archive.
+    pub total_bytes: u64,
+    pub current_file: Option<String>,
+}
+
+/// A running extraction, which `Extractions::cancel` stops before its next
+/// entry or chunk.
+pub struct Extraction {
+    pub id: String,
+    cancelled: Arc<AtomicBool>,
+}
+
+impl Extraction {
+    fn is_cancelled(&self) -> bool {
+        self.cancelled.load(Ordering::Relaxed)
+    }
+}
+
+/// Extractions in progress by id, kept in the app state.
+#[derive(Default)]
+pub struct Extractions {
+    running: Mutex<HashMap<String, Arc<AtomicBool>>>,
+}
+
+impl Extractions {
+    pub fn start(&self, id: String) -> Result<Extraction, String> {
+        let mut running = self.running.lock().map_err(|e| e.to_string())?;
+        if running.contains_key(&id) {
+            return Err(format!("Extraction '{}' is already running", id));
+        }
+        let cancelled = Arc::new(AtomicBool::new(false));
+        running.insert(id.clone(), Arc::clone(&cancelled));
+        Ok(Extraction { id, cancelled })
+    }
+
+    /// Asks a running extraction to stop; false if none has this id.
+    pub fn cancel(&self, id: &str) -> bool {
+        let running = self.running.lock();
+        match running.ok().as_ref().and_then(|running| running.get(id)) {
+            Some(cancelled) => {
+                cancelled.store(true, Ordering::Relaxed);
+                true
+            }
+            None => false,
+        }
+    }
+
+    pub fn finish(&self, id: &str) {
+        if let Ok(mut running) = self.running.lock() {
+            running.remove(id);
+        }
+    }
+}
+
+impl ExtractSummary {
+    fn skip(&mut self, name: &str, reason: &str) {
+        self.skipped.push(SkippedEntry {
+            name: name.to_string(),
+            reason: reason.to_string(),
+        });
+    }
+}
+
+// -----------------
+// Extraction
+// -----------------
+
+/// Extracts `zip_path` into `destination`, reporting progress through
+/// `on_progress`. Unsafe entries are skipped and listed in the summary; an
+/// archive over the limits is refused before anything is written. When the
+/// extraction fails or is cancelled, the files and folders it created are
+/// removed again.
+pub fn extract_zip(
+    zip_path: &Path,
+    destination: &Path,
+    extraction: &Extraction,
+    on_progress: &mut dyn FnMut(&ExtractProgress),
+) -> Result<ExtractSummary, String> {
+    extract_within(zip_path, destination, extraction, on_progress, &LIMITS)
+}
+
+fn extract_within(
+    zip_path: &Path,
+    destination: &Path,
+    extraction: &Extraction,
+    on_progress: &mut dyn FnMut(&ExtractProgress),
+    limits: &Limits,
+) -> Result<ExtractSummary, String> {
+    let file = fs::File::open(zip_path).map_err(|e| format!("Failed to open zip file: {}", e))?;
+    let mut archive = ZipArchive::new(file).map_err(|e| format!("Failed to parse zip: {}", e))?;
+    let total_bytes = check_limits(&mut archive, limits)?;
+
+    let mut created = Vec::new();
+    let root = match create_destination(destination, &mut created) {
+        Ok(root) => root,
+        Err(error) => {
+            remove_created(&created);
+            return Err(error);
+        }
+    };
+    let mut extractor = Extractor {
+        root,
+        extraction,
+        limits,
+        progress: ExtractProgress {
+            extraction_id: extraction.id.clone(),
+            entries_done: 0,
+            entry_count: archive.len(),
+            bytes_written: 0,
+            total_bytes,
+            current_file: None,
+        },
+        on_progress,
+        last_report: None,
+        created,
+        summary: ExtractSummary::default(),
+    };
+
+    let result = (0..archive.len()).try_for_each(|index| {
+        extractor.check_cancelled()?;
+        let mut entry = archive
+            .by_index(index)
+            .map_err(|e| format!("Failed to access zip entry {}: {}", index, e))?;
+        extractor.extract_entry(&mut entry)?;
+        extractor.progress.entries_done += 1;
+        extractor.report(false);
+        Ok(())
+    });
+    extractor.progress.current_file = None;
+    extractor.report(true);
+    match result {
+        Ok(()) => Ok(extractor.summary),
+        Err(error) => {
+            remove_created(&extractor.created);
+            Err(error)
+        }
+    }
+}
+
+fn create_destination(destination: &Path, created: &mut Vec<PathBuf>) -> Result<PathBuf, String> {
+    record_missing_dirs(destination, created);
+    fs::create_dir_all(destination)
+        .map_err(|e| format!("Failed to create destination directory: {}", e))?;
+    destination
+        .canonicalize()
+        .map_err(|e| format!("Failed to resolve destination directory: {}", e))
+}
+
+struct Extractor<'a> {
+    /// Canonical destination folder.
+    root: PathBuf,
+    extraction: &'a Extraction,
+    limits: &'a Limits,
+    progress: ExtractProgress,
+    on_progress: &'a mut dyn FnMut(&ExtractProgress),
+    last_report: Option<Instant>,
+    /// Entries that did not exist before, in creation order.
+    created: Vec<PathBuf>,
+    summary: ExtractSummary,
+}
+
+impl Extractor<'_> {
+    fn check_cancelled(&self) -> Result<(), String> {
+        if self.extraction.is_cancelled() {
+            Err(CANCELLED.to_string())
+        } else {
+            Ok(())
+        }
+    }
+
+    /// Reports progress, at most every `PROGRESS_INTERVAL` unless `force`d.
+    fn report(&mut self, force: bool) {
+        let now = Instant::now();
+        let due = self
+            .last_report
+            .map_or(true, |last| now.duration_since(last) >= PROGRESS_INTERVAL);
+        if force || due {
+            self.last_report = Some(now);
+            (self.on_progress)(&self.progress);
+        }
+    }
+
+    fn extract_entry(&mut self, entry: &mut ZipFile) -> Result<(), String> {
+        let name = entry.name().to_string();
+        self.progress.current_file = Some(name.clone());
+        self.report(false);
+
+        let Some(relative) = entry.enclosed_name().map(Path::to_path_buf) else {
+            self.summary
+                .skip(&name, "Its path leads outside the destination");
+            return Ok(());
+        };
+        let outpath = self.root.join(&relative);
+
+        let folder = if entry.is_dir() {
+            outpath.clone()
+        } else {
+            outpath.parent().unwrap_or(&self.root).to_path_buf()
+        };
+        if !create_dir_inside(&self.root, &folder, &mut self.created)? {
+            self.summary.skip(
+                &name,
+                "Its path leads outside the destination through a symbolic link",
+            );
+            return Ok(());
+        }
+        if entry.is_dir() {
+            self.summary.directory_count += 1;
+            return Ok(());
+        }
+        let existing = fs::symlink_metadata(&outpath).ok();
+        if existing
+            .as_ref()
+            .is_some_and(|metadata| metadata.file_type().is_symlink())
+        {
+            self.summary.skip(&name, "It would replace a symbolic link");
+            return Ok(());
+        }
+
+        if entry
+            .unix_mode()
+            .is_some_and(|mode| mode & FILE_TYPE_MASK == SYMLINK_MODE)
+        {
+            return self.extract_link(entry, &name, &relative, &outpath);
+        }
+
+        let mut outfile = fs::File::create(&outpath)
+            .map_err(|e| format!("Failed to create file {}: {}", outpath.display(), e))?;
+        if existing.is_none() {
+            self.created.push(outpath.clone());
+        }
+        self.copy(entry, &mut outfile, &outpath)?;
+        self.summary.file_count += 1;
+        Ok(())
+    }
+
+    fn extract_link(
+        &mut self,
+        entry: &mut ZipFile,
+        name: &str,
+        relative: &Path,
+        outpath: &Path,
+    ) -> Result<(), String> {
+        let mut target = String::new();
+        entry
+            .take(MAX_LINK_TARGET_SIZE)
+            .read_to_string(&mut target)
+            .map_err(|e| format!("Failed to read link {}: {}", name, e))?;
+        let target = PathBuf::from(target);
+        if !link_stays_inside(relative, &target) {
+            self.summary.skip(
+                name,
+                "It is a symbolic link pointing outside the destination",
+            );
+            return Ok(());
+        }
+        let is_directory = outpath
+            .parent()
+            .map(|parent| parent.join(&target))
+            .is_some_and(|resolved| resolved.is_dir());
+        if create_symlink(&target, outpath, is_directory).is_err() {
+            self.summary
+                .skip(name, "Symbolic links cannot be created here");
+            return Ok(());
+        }
+        self.created.push(outpath.to_path_buf());
+        self.summary.link_count += 1;
+        Ok(())
+    }
+
+    /// Copies in chunks so cancellation and progress apply within large
+    /// files. Declared sizes can lie, so the size limit is enforced on what
+    /// is actually written.
+    fn copy(
+        &mut self,
+        entry: &mut ZipFile,
+        outfile: &mut fs::File,
+        outpath: &Path,
+    ) -> Result<(), String> {
+        let failed =
+            |e: std::io::Error| format!("Failed to write to file {}: {}", outpath.display(), e);
+        let mut buffer = vec![0; COPY_CHUNK_SIZE];
+        loop {
+            self.check_cancelled()?;
+            let read = entry.read(&mut buffer).map_err(failed)?;
+            if read == 0 {
+                return Ok(());
+            }
+            self.progress.bytes_written += read as u64;
+            if self.progress.bytes_written > self.limits.max_total_size {
+                return Err(too_large(self.limits));
+            }
+            outfile.write_all(&buffer[..read]).map_err(failed)?;
+            self.report(false);
+        }
+    }
+}
+
+/// Removes what an interrupted extraction created, newest first. Files that
+/// were overwritten keep their new content.
+fn remove_created(created: &[PathBuf]) {
+    for path in created.iter().rev() {
+        let is_dir = fs::symlink_metadata(path).is_ok_and(|metadata| metadata.is_dir());
+        let _ = if is_dir {
+            fs::remove_dir(path)
+        } else {
+            fs::remove_file(path)
+        };
+    }
+}
+
+/// Records the folders `create_dir_all(dir)` is about to create, outermost
+/// first.
+fn record_missing_dirs(dir: &Path, created: &mut Vec<PathBuf>) {
+    let missing: Vec<PathBuf> = dir
+        .ancestors()
+        .take_while(|ancestor| fs::symlink_metadata(ancestor).is_err())
+        .map(Path::to_path_buf)
+        .collect();
+    created.extend(missing.into_iter().rev());
+}
+
+fn too_large(limits: &Limits) -> String {
+    format!(
+        "Archive is larger than {} MB when extracted",
+        limits.max_total_size / (1024 * 1024)
+    )
+}
+
+/// Returns the declared uncompressed size of the archive.
+fn check_limits(archive: &mut ZipArchive<fs::File>, limits: &Limits) -> Result<u64, String> {
+    if archive.len() > limits.max_entries {
+        return Err(format!(
+            "Archive has {} entries; at most {} are allowed",
+            archive.len(),
+            limits.max_entries
+        ));
+    }
+    let mut total: u64 = 0;
+    for index in 0..archive.len() {
+        let entry = archive
+            .by_index_raw(index)
+            .map_err(|e| format!("Failed to access zip entry {}: {}", index, e))?;
+        total = total.saturating_add(entry.size());
+        if total > limits.max_total_size {
+            return Err(too_large(limits));
+        }
+    }
+    Ok(total)
+}
+
+/// Creates `dir` unless its closest existing ancestor resolves outside
+/// `root`, which must be canonical. Returns whether `dir` is usable.
+fn create_dir_inside(root: &Path, dir: &Path, created: &mut Vec<PathBuf>) -> Result<bool, String> {
+    let existing = dir.ancestors().find(|ancestor| ancestor.exists());
+    let inside = existing
+        .and_then(|ancestor| ancestor.canonicalize().ok())
+        .is_some_and(|resolved| resolved.starts_with(root));
+    if !inside {
+        return Ok(false);
+    }
+    record_missing_dirs(dir, created);
+    fs::create_dir_all(dir)
+        .map_err(|e| format!("Failed to create directory {}: {}", dir.display(), e))?;
+    Ok(dir
+        .canonicalize()
+        .is_ok_and(|resolved| resolved.starts_with(root)))
+}
+
+/// Whether a link at `link` (relative to the destination) pointing at
+/// `target` resolves inside the destination, judging by the names alone.
+fn link_stays_inside(link: &Path, target: &Path) -> bool {
+    if target.is_absolute() || target.has_root() {
+        return false;
+    }
+    let mut depth = link.components().count().saturating_sub(1);
+    for component in target.components() {
+        match component {
+            Component::Normal(_) => depth += 1,
+            Component::CurDir => {}
+            Component::ParentDir => match depth.checked_sub(1) {
+                Some(parent) => depth = parent,
+                None => return false,
+            },
+            Component::RootDir | Component::Prefix(_) => return false,
+        }
+    }
+    true
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use zip::write::FileOptions;
+    use zip::ZipWriter;
+
+    /// Writes an archive of `(name, content)` files and `(name, target)`
+    /// symbolic links.
+    fn archive(dir: &Path, files: &[(&str, &str)], links: &[(&str, &str)]) -> PathBuf {
+        let path = dir.join("archive.zip");
+        let mut writer = ZipWriter::new(fs::File::create(&path).unwrap());
+        for (name, content) in files {
+            writer.start_file(*name, FileOptions::default()).unwrap();
+            writer.write_all(content.as_bytes()).unwrap();
+        }
+        for (name, target) in links {
+            writer
+                .add_symlink(*name, *target, FileOptions::default())
+                .unwrap();
+        }
+        writer.finish().unwrap();
+        path
+    }
+
+    fn extract(
+        zip_path: &Path,
+        destination: &Path,
+        limits: &Limits,
+    ) -> Result<ExtractSummary, String> {
+        let extractions = Extractions::default();
+        let extraction = extractions.start("test".to_string()).unwrap();
+        extract_within(zip_path, destination, &extraction, &mut |_| {}, limits)
+    }
+
+    fn skipped(summary: &ExtractSummary) -> Vec<&str> {
+        summary
+            .skipped
+            .iter()
+            .map(|entry| entry.name.as_str())
+            .collect()
+    }
+
+    #[test]
+    fn entries_leaving_the_destination_are_skipped() {
+        let dir = tempfile::tempdir().unwrap();
+        let destination = dir.path().join("destination");
+        let zip_path = archive(
+            dir.path(),
+            &[
+                ("../parent.txt", "x"),
+                ("/absolute.txt", "x"),
+                ("kept.txt", "ok"),
+            ],
+            &[],
+        );
+
+        let summary = extract(&zip_path, &destination, &LIMITS).unwrap();
+        assert_eq!(skipped(&summary), ["../parent.txt", "/absolute.txt"]);
+        assert_eq!(summary.file_count, 1);
+        assert!(!dir.path().join("parent.txt").exists());
+        assert!(!Path::new("/absolute.txt").exists());
+        assert_eq!(
+            fs::read_to_string(destination.join("kept.txt")).unwrap(),
+            "ok"
+        );
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn symbolic_links_must_point_inside() {
+        let dir = tempfile::tempdir().unwrap();
+        let destination = dir.path().join("destination");
+        let zip_path = archive(
+            dir.path(),
+            &[("docs/readme.txt", "ok")],
+            &[
+                ("docs/up", "../../outside"),
+                ("docs/root", "/etc"),
+                ("docs/latest", "readme.txt"),
+            ],
+        );
+
+        let summary = extract(&zip_path, &destination, &LIMITS).unwrap();
+        assert_eq!(skipped(&summary), ["docs/up", "docs/root"]);
+        assert_eq!(summary.link_count, 1);
+        assert_eq!(
+            fs::read_to_string(destination.join("docs/latest")).unwrap(),
+            "ok"
+        );
+        assert!(fs::symlink_metadata(destination.join("docs/up")).is_err());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn writes_through_a_linked_folder_are_skipped() {
+        let dir = tempfile::tempdir().unwrap();
+        let destination = dir.path().join("destination");
+        let outside = dir.path().join("outside");
+        fs::create_dir_all(&destination).unwrap();
+        fs::create_dir(&outside).unwrap();
+        std::os::unix::fs::symlink(&outside, destination.join("linked")).unwrap();
+        let zip_path = archive(
+            dir.path(),
+            &[("linked/file.txt", "x"), ("linked/nested/file.txt", "x")],
+            &[],
+        );
+
+        let summary = extract(&zip_path, &destination, &LIMITS).unwrap();
+        assert_eq!(
+            skipped(&summary),
+            ["linked/file.txt", "linked/nested/file.txt"]
+        );
+        assert_eq!(fs::read_dir(&outside).unwrap().count(), 0);
+    }
+
+    #[test]
+    fn archives_over_the_limits_are_refused_before_writing() {
+        let dir = tempfile::tempdir().unwrap();
+        let destination = dir.path().join("destination");
+        let zip_path = archive(dir.path(), &[("a.txt", "aaaa"), ("b.txt", "bbbb")], &[]);
+
+        let few_entries = Limits {
+            max_entries: 1,
+            max_total_size: LIMITS.max_total_size,
+        };
+        assert_eq!(
+            extract(&zip_path, &destination, &few_entries).unwrap_err(),
+            "Archive has 2 entries; at most 1 are allowed"
+        );
+
+        let few_bytes = Limits {
+            max_entries: LIMITS.max_entries,
+            max_total_size: 6,
+        };
+        assert_eq!(
+            extract(&zip_path, &destination, &few_bytes).unwrap_err(),
+            too_large(&few_bytes)
+        );
+        assert!(!destination.exists());
+
+        assert!(extract(&zip_path, &destination, &LIMITS).is_ok());
+    }
+}
@@ -0,0 +1,339 @@
+// Entry filtering shared by the directory listing and scanning commands:
+// dotfiles, `.gitignore`/`.ignore` files and user-supplied glob lists.
+
+use globset::{Glob, GlobBuilder, GlobMatcher, GlobSet, GlobSetBuilder};
+use serde::Deserialize;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+const IGNORE_FILES: [&str; 2] = [".gitignore", ".ignore"];
+
+// -----------------
+// Types
+// -----------------
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct FilterOptions {
+    /// Keep entries whose name starts with a dot.
+    pub show_hidden: bool,
+    /// Skip entries ignored by `.gitignore`/`.ignore` files in the tree.
+    pub respect_gitignore: bool,
+    /// Only files matching one of these globs are kept (all when empty).
+    pub include: Vec<String>,
+    /// Files and folders matching any of these globs are left out.
+    pub exclude: Vec<String>,
+}
+
+/// Glob patterns without a `/` match an entry's name at any depth; patterns
+/// with one match its path relative to the listed root.
+pub struct PatternSet {
+    names: GlobSet,
+    paths: GlobSet,
+    is_empty: bool,
+}
+
+impl PatternSet {
+    pub fn new(patterns: &[String]) -> Result<Self, String> {
+        let mut names = GlobSetBuilder::new();
+        let mut paths = GlobSetBuilder::new();
+        for pattern in patterns.iter().map(|p| p.trim()).filter(|p| !p.is_empty()) {
+            let trimmed = pattern.trim_start_matches('/').trim_end_matches('/');
+            let glob =
+                Glob::new(trimmed).map_err(|e| format!("Invalid pattern '{}': {}", pattern, e))?;
+            if pattern.contains('/') {
+                paths.add(glob);
+            } else {
+                names.add(glob);
+            }
+        }
+        Ok(PatternSet {
+            names: names.build().map_err(|e| e.to_string())?,
+            paths: paths.build().map_err(|e| e.to_string())?,
+            is_empty: patterns.iter().all(|p| p.trim().is_empty()),
+        })
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.is_empty
+    }
+
+    pub fn matches(&self, name: &str, relative_path: &str) -> bool {
+        self.names.is_match(name) || self.paths.is_match(relative_path)
+    }
+}
+
+struct IgnoreRule {
+    matcher: GlobMatcher,
+    negated: bool,
+    directory_only: bool,
+}
+
+/// The rules of one ignore file, matched against paths relative to the folder
+/// that holds it.
+struct IgnoreFile {
+    base: PathBuf,
+    rules: Vec<IgnoreRule>,
+}
+
+/// Decides which entries of a directory tree are kept. Ignore files are
+/// picked up as folders are entered with [`EntryFilter::enter`].
+pub struct EntryFilter {
+    show_hidden: bool,
+    respect_gitignore: bool,
+    include: PatternSet,
+    exclude: PatternSet,
+    ignore_files: Vec<IgnoreFile>,
+}
+
+// -----------------
+// Filtering
+// -----------------
+impl EntryFilter {
+    /// Builds a filter for listing `root`. When honoring ignore files, those of
+    /// the enclosing git repository's folders above `root` apply as well.
+    pub fn new(root: &Path, options: &FilterOptions) -> Result<Self, String> {
+        let mut filter = EntryFilter {
+            show_hidden: options.show_hidden,
+            respect_gitignore: options.respect_gitignore,
+            include: PatternSet::new(&options.include)?,
+            exclude: PatternSet::new(&options.exclude)?,
+            ignore_files: Vec::new(),
+        };
+        if filter.respect_gitignore {
+            let repository_root = root.ancestors().find(|dir| dir.join(".git").exists());
+            if let Some(repository_root) = repository_root {
+                let mut ancestors: Vec<&Path> = root
+                    .ancestors()
+                    .skip(1)
+                    .take_while(|dir| dir.starts_with(repository_root))
+                    .collect();
+                ancestors.reverse();
+                for ancestor in ancestors {
+                    filter.load_ignore_files(ancestor);
+                }
+            }
+            filter.load_ignore_files(root);
+        }
+        Ok(filter)
+    }
+
+    /// Loads the ignore files of a folder about to be listed. Returns how many
+    /// were added so the caller can [`EntryFilter::leave`] it afterwards.
+    pub fn enter(&mut self, dir: &Path) -> usize {
+        if self.respect_gitignore {
+            self.load_ignore_files(dir)
+        } else {
+            0
+        }
+    }
+
+    pub fn leave(&mut self, loaded: usize) {
+        let remaining = self.ignore_files.len().saturating_sub(loaded);
+        self.ignore_files.truncate(remaining);
+    }
+
+    fn load_ignore_files(&mut self, dir: &Path) -> usize {
+        let mut loaded = 0;
+        for file_name in IGNORE_FILES {
+            if let Ok(content) = fs::read_to_string(dir.join(file_name)) {
+                self.ignore_files.push(IgnoreFile {
+                    base: dir.to_path_buf(),
+                    rules: parse_ignore_file(&content),
+                });
+                loaded += 1;
+            }
+        }
+        loaded
+    }
+
+    /// Whether an entry is excluded by the dotfile, ignore-file or exclude
+    /// rules. Folders are pruned with everything below them.
+    pub fn is_excluded(&self, path: &Path, relative_path: &str, is_directory: bool) -> bool {
+        let name = path
+            .file_name()
+            .map(|name| name.to_string_lossy().into_owned())
+            .unwrap_or_default();
+        if !self.show_hidden && name.starts_with('.') {
+            return true;
+        }
+        if self.respect_gitignore && (name == ".git" || self.is_ignored(path, is_directory)) {
+            return true;
+        }
+        self.exclude.matches(&name, relative_path)
+    }
+
+    /// Whether a file passes the include list.
+    pub fn is_included(&self, name: &str, relative_path: &str) -> bool {
+        self.include.is_empty() || self.include.matches(name, relative_path)
+    }
+
+    pub fn has_include_patterns(&self) -> bool {
+        !self.include.is_empty()
+    }
+
+    /// Applies ignore rules like git does: deeper files override shallower
+    /// ones and, within a file, the last matching rule wins.
+    fn is_ignored(&self, path: &Path, is_directory: bool) -> bool {
+        let mut ignored = false;
+        for file in &self.ignore_files {
+            let Ok(relative) = path.strip_prefix(&file.base) else {
+                continue;
+            };
+            let relative = relative.to_string_lossy().replace('\\', "/");
+            for rule in &file.rules {
+                if rule.directory_only && !is_directory {
+                    continue;
+                }
+                if rule.matcher.is_match(&relative) {
+                    ignored = !rule.negated;
+                }
+            }
+        }
+        ignored
+    }
+}
+
+fn parse_ignore_file(content: &str) -> Vec<IgnoreRule> {
+    content
+        .lines()
+        .filter_map(|line| {
+            let line = line.trim_end();
+            if line.is_empty() || line.starts_with('#') {
+                return None;
+            }
+            let (negated, pattern) = match line.strip_prefix('!') {
+                Some(rest) => (true, rest),
+                None => (false, line.strip_prefix('\\').unwrap_or(line)),
+            };
+            let directory_only = pattern.ends_with('/');
+            let pattern = pattern.trim_end_matches('/');
+            // A slash anywhere but the end anchors the pattern to the file's folder.
+            let glob = if pattern.contains('/') {
+                pattern.trim_start_matches('/').to_string()
+            } else {
+                format!("**/{}", pattern)
+            };
+            let matcher = GlobBuilder::new(&glob)
+                .literal_separator(true)
+                .build()
+                .ok()?
+                .compile_matcher();
+            Some(IgnoreRule {
+                matcher,
+                negated,
+                directory_only,
+            })
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn gitignore_filter(root: &Path) -> EntryFilter {
+        let options = FilterOptions {
+            respect_gitignore: true,
+            ..FilterOptions::default()
+        };
+        EntryFilter::new(root, &options).unwrap()
+    }
+
+    #[test]
+    fn ignore_rules_follow_git_semantics() {
+        let dir = tempfile::tempdir().unwrap();
+        let root = dir.path();
+        fs::write(
+            root.join(".gitignore"),
+            "# comment\n*.log\n!keep.log\n/build\ndist/\ndocs/*.tmp\n\\!important\n",
+        )
+        .unwrap();
+        let filter = gitignore_filter(root);
+        let ignored = |relative: &str, is_directory: bool| {
+            filter.is_excluded(&root.join(relative), relative, is_directory)
+        };
+
+        // Negation: the last matching rule wins.
+        assert!(ignored("src/debug.log", false));
+        assert!(!ignored("src/keep.log", false));
+        // A leading slash anchors to the ignore file's folder.
+        assert!(ignored("build", true));
+        assert!(!ignored("src/build", true));
+        // A trailing slash only matches folders, at any depth.
+        assert!(ignored("dist", true));
+        assert!(ignored("src/dist", true));
+        assert!(!ignored("dist", false));
+        // A slash in the middle anchors too, and `*` stops at separators.
+        assert!(ignored("docs/draft.tmp", false));
+        assert!(!ignored("docs/old/draft.tmp", false));
+        assert!(!ignored("src/docs/draft.tmp", false));
+        // An escaped `!` is a literal name.
+        assert!(ignored("!important", false));
+        assert!(ignored(".git", true));
+        assert!(!ignored("comment", false));
+    }
+
+    #[test]
+    fn nested_ignore_files_apply_only_inside_their_folder() {
+        let dir = tempfile::tempdir().unwrap();
+        let root = dir.path();
+        fs::create_dir_all(root.join("docs")).unwrap();
+        fs::create_dir_all(root.join("src")).unwrap();
+        fs::write(root.join(".gitignore"), "*.txt\n").unwrap();
+        fs::write(root.join("docs").join(".ignore"), "!notes.txt\n*.md\n").unwrap();
+        let mut filter = gitignore_filter(root);
+        let ignored = |filter: &EntryFilter, relative: &str| {
+            filter.is_excluded(&root.join(relative), relative, false)
+        };
+
+        let loaded = filter.enter(&root.join("docs"));
+        assert_eq!(loaded, 1);
+        assert!(!ignored(&filter, "docs/notes.txt"));
+        assert!(ignored(&filter, "docs/other.txt"));
+        assert!(ignored(&filter, "docs/guide.md"));
+        filter.leave(loaded);
+
+        let loaded = filter.enter(&root.join("src"));
+        assert_eq!(loaded, 0);
+        assert!(ignored(&filter, "src/notes.txt"));
+        assert!(!ignored(&filter, "src/guide.md"));
+    }
+
+    #[test]
+    fn ignore_files_above_the_root_apply_inside_a_repository() {
+        let dir = tempfile::tempdir().unwrap();
+        let repository = dir.path();
+        fs::create_dir_all(repository.join(".git")).unwrap();
+        fs::create_dir_all(repository.join("app").join("src")).unwrap();
+        fs::write(repository.join(".gitignore"), "app/src/*.gen.rs\n*.bak\n").unwrap();
+        let root = repository.join("app");
+        let filter = gitignore_filter(&root);
+
+        assert!(filter.is_excluded(&root.join("src/lib.gen.rs"), "src/lib.gen.rs", false));
+        assert!(filter.is_excluded(&root.join("old.bak"), "old.bak", false));
+        assert!(!filter.is_excluded(&root.join("src/lib.rs"), "src/lib.rs", false));
+    }
+
+    #[test]
+    fn hidden_include_and_exclude_filters() {
+        let dir = tempfile::tempdir().unwrap();
+        let root = dir.path();
+        let options = FilterOptions {
+            include: vec!["*.rs".to_string(), "docs/*.md".to_string()],
+            exclude: vec!["target".to_string(), "/src/gen/".to_string()],
+            ..FilterOptions::default()
+        };
+        let filter = EntryFilter::new(root, &options).unwrap();
+
+        assert!(filter.is_excluded(&root.join(".env"), ".env", false));
+        assert!(filter.is_excluded(&root.join("target"), "target", true));
+        assert!(filter.is_excluded(&root.join("a/target"), "a/target", true));
+        assert!(filter.is_excluded(&root.join("src/gen"), "src/gen", true));
+        assert!(!filter.is_excluded(&root.join("gen"), "gen", true));
+        assert!(filter.is_included("main.rs", "src/main.rs"));
+        assert!(filter.is_included("guide.md", "docs/guide.md"));
+        assert!(!filter.is_included("notes.md", "notes.md"));
+        assert!(filter.has_include_patterns());
+        assert!(PatternSet::new(&["[".to_string()]).is_err());
+    }
+}
@@ -0,0 +1,408 @@
+// `@include` directives: a line `@include Name` is replaced by the body of
+// another template, indented to the directive's level.
+//
+//   project
+//   	@include Docs                   same category first, then top level
+//   	@include Shared/Assets          category path from the templates folder
+//
+// Every template source is searched, in the order its templates take
+// precedence, as in the merged template list.
+//
+// Variables declared in an included template's frontmatter are added to the
+// including template's, whose own declarations win; the rest of the included
+// frontmatter is dropped. Includes nest; a template that ends up including
+// itself is reported with the whole chain.
+
+use serde_yaml::{Mapping, Value};
+use std::fs;
+use std::path::PathBuf;
+
+use crate::structure;
+use crate::templates;
+
+const INCLUDE_DIRECTIVE: &str = "@include";
+const VARIABLES_KEY: &str = "variables";
+
+/// A template taking part in an include chain.
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct TemplateId {
+    category: Option<String>,
+    name: String,
+}
+
+impl TemplateId {
+    fn label(&self) -> String {
+        match &self.category {
+            Some(category) => format!("{}/{}", category, self.name),
+            None => self.name.clone(),
+        }
+    }
+}
+
+/// Expands every include in `content`, looking templates up in `template_dirs`
+/// in order. `category` and `name` identify the template being expanded, if it
+/// is one, for lookups and cycle detection.
+pub fn expand_includes(
+    template_dirs: &[PathBuf],
+    content: &str,
+    category: Option<&str>,
+    name: Option<&str>,
+) -> Result<String, String> {
+    if !content.contains(INCLUDE_DIRECTIVE) {
+        return Ok(content.to_string());
+    }
+    let mut chain: Vec<TemplateId> = name
+        .map(|name| TemplateId {
+            category: category.map(str::to_string),
+            name: name.to_string(),
+        })
+        .into_iter()
+        .collect();
+    let (frontmatter, body) = structure::split_frontmatter(content).map_err(|e| e.to_string())?;
+    let header = &content[..content.len() - body.len()];
+    let mut included_declarations = Mapping::new();
+    let expanded = expand_body(
+        template_dirs,
+        body,
+        category,
+        &mut chain,
+        &mut included_declarations,
+    )?;
+    let raw = frontmatter.map(|frontmatter| frontmatter.raw);
+    match merge_declarations(raw.as_deref(), included_declarations)? {
+        Some(merged) => Ok(format!("---\n{}---\n{}", merged, expanded)),
+        None => Ok(format!("{}{}", header, expanded)),
+    }
+}
+
+/// Every template `content` includes, directly or through other includes, as
+/// `(category, name, content)` in the order they are found. Each
+/// template is listed once, so cycles end the walk instead of failing it.
+pub fn included_templates(
+    template_dirs: &[PathBuf],
+    content: &str,
+    category: Option<&str>,
+) -> Result<Vec<(Option<String>, String, String)>, String> {
+    let mut found: Vec<(TemplateId, String)> = Vec::new();
+    let mut pending = vec![(category.map(str::to_string), content.to_string())];
+    while let Some((category, content)) = pending.pop() {
+        let (_, body) = structure::split_frontmatter(&content).map_err(|e| e.to_string())?;
+        for reference in body_references(body) {
+            let reference = reference
+                .ok_or_else(|| format!("'{}' needs a template name", INCLUDE_DIRECTIVE))?;
+            let (included, included_content) =
+                find_template(template_dirs, category.as_deref(), reference)
+                    .ok_or_else(|| format!("Included template not found: {}", reference))?;
+            if found.iter().any(|(known, _)| *known == included) {
+                continue;
+            }
+            pending.push((included.category.clone(), included_content.clone()));
+            found.push((included, included_content));
+        }
+    }
+    Ok(found
+        .into_iter()
+        .map(|(id, content)| (id.category, id.name, content))
+        .collect())
+}
+
+/// The include references of `body`, outside content blocks.
+fn body_references(body: &str) -> Vec<Option<&str>> {
+    let mut references = Vec::new();
+    let mut open_block: Option<&str> = None;
+    for text in body.lines() {
+        let entry = text.trim_start_matches('\t');
+        if let Some(delimiter) = open_block {
+            if entry == delimiter {
+                open_block = None;
+            }
+            continue;
+        }
+        match include_reference(entry) {
+            Some(reference) => references.push(reference),
+            None => open_block = structure::content_delimiter(text),
+        }
+    }
+    references
+}
+
+/// The `variables` mapping of a frontmatter block.
+fn variable_declarations(frontmatter: &str) -> Result<Mapping, String> {
+    if frontmatter.trim().is_empty() {
+        return Ok(Mapping::new());
+    }
+    let value: Value =
+        serde_yaml::from_str(frontmatter).map_err(|e| format!("Invalid frontmatter: {}", e))?;
+    match value.get(VARIABLES_KEY) {
+        None | Some(Value::Null) => Ok(Mapping::new()),
+        Some(Value::Mapping(declarations)) => Ok(declarations.clone()),
+        Some(_) => Err("Frontmatter 'variables' must be a mapping".to_string()),
+    }
+}
+
+/// Adds the declarations of included templates that `frontmatter` does not
+/// declare itself. Returns the new frontmatter text, or `None` when nothing
+/// was added and the original can be kept as written.
+fn merge_declarations(
+    frontmatter: Option<&str>,
+    included: Mapping,
+) -> Result<Option<String>, String> {
+    let declared = variable_declarations(frontmatter.unwrap_or(""))?;
+    let added: Vec<(Value, Value)> = included
+        .into_iter()
+        .filter(|(name, _)| !declared.contains_key(name))
+        .collect();
+    if added.is_empty() {
+        return Ok(None);
+    }
+    let mut value: Value = match frontmatter.filter(|raw| !raw.trim().is_empty()) {
+        Some(raw) => {
+            serde_yaml::from_str(raw).map_err(|e| format!("Invalid frontmatter: {}", e))?
+        }
+        None => Value::Mapping(Mapping::new()),
+    };
+    let Value::Mapping(root) = &mut value else {
+        return Err("Frontmatter must be a mapping".to_string());
+    };
+    let mut declarations = declared;
+    declarations.extend(added);
+    root.insert(
+        Value::String(VARIABLES_KEY.to_string()),
+        Value::Mapping(declarations),
+    );
+    serde_yaml::to_string(&value)
+        .map(Some)
+        .map_err(|e| format!("Invalid frontmatter: {}", e))
+}
+
+/// `declarations` collects the variables declared by included templates; the
+/// first declaration of a name wins, so an including template overrides the
+/// templates it includes.
+fn expand_body(
+    template_dirs: &[PathBuf],
+    body: &str,
+    category: Option<&str>,
+    chain: &mut Vec<TemplateId>,
+    declarations: &mut Mapping,
+) -> Result<String, String> {
+    let mut expanded = String::with_capacity(body.len());
+    // Delimiter of the content block being copied, whose lines stay as they are.
+    let mut open_block: Option<String> = None;
+
+    for line in body.split_inclusive('\n') {
+        let text = line.trim_end_matches(['\n', '\r']);
+        if let Some(delimiter) = &open_block {
+            if text.trim_start_matches('\t') == delimiter {
+                open_block = None;
+            }
+            expanded.push_str(line);
+            continue;
+        }
+        let entry = text.trim_start_matches('\t');
+        let Some(reference) = include_reference(entry) else {
+            open_block = structure::content_delimiter(text).map(str::to_string);
+            expanded.push_str(line);
+            continue;
+        };
+
+        let reference = reference.ok_or_else(|| {
+            with_chain(
+                format!("'{}' needs a template name", INCLUDE_DIRECTIVE),
+                chain,
+            )
+        })?;
+        let (included, content) =
+            find_template(template_dirs, category, reference).ok_or_else(|| {
+                with_chain(format!("Included template not found: {}", reference), chain)
+            })?;
+        if chain.contains(&included) {
+            let cycle: Vec<String> = chain
+                .iter()
+                .chain(std::iter::once(&included))
+                .map(TemplateId::label)
+                .collect();
+            return Err(format!("Include cycle: {}", cycle.join(" → ")));
+        }
+
+        let (included_frontmatter, included_body) = structure::split_frontmatter(&content)
+            .map_err(|e| with_chain(format!("{}: {}", included.label(), e), chain))?;
+        if let Some(included_frontmatter) = included_frontmatter {
+            let included_declarations = variable_declarations(&included_frontmatter.raw)
+                .map_err(|e| with_chain(format!("{}: {}", included.label(), e), chain))?;
+            for (name, declaration) in included_declarations {
+                if !declarations.contains_key(&name) {
+                    declarations.insert(name, declaration);
+                }
+            }
+        }
+        chain.push(included.clone());
+        let included_body = expand_body(
+            template_dirs,
+            included_body,
+            included.category.as_deref(),
+            chain,
+            declarations,
+        )?;
+        chain.pop();
+
+        let indent = &text[..text.len() - entry.len()];
+        let mut lines = included_body.split_inclusive('\n').peekable();
+        // Delimiter and content indentation of a block in the included body.
+        let mut in_block: Option<(String, String)> = None;
+        while let Some(included_line) = lines.next() {
+            let included_text = included_line.trim_end_matches(['\n', '\r']);
+            let indented = match &in_block {
+                Some((delimiter, _)) if included_text.trim_start_matches('\t') == delimiter => {
+                    in_block = None;
+                    true
+                }
+                // Only lines carrying the block's indentation are shifted, so
+                // the parser strips exactly what was added.
+                Some((_, block_indent)) => included_text.starts_with(block_indent.as_str()),
+                None => {
+                    in_block = structure::content_delimiter(included_text).map(|delimiter| {
+                        let level =
+                            included_text.len() - included_text.trim_start_matches('\t').len();
+                        (delimiter.to_string(), "\t".repeat(level + 1))
+                    });
+                    !included_text.trim().is_empty()
+                }
+            };
+            if indented {
+                expanded.push_str(indent);
+            }
+            expanded.push_str(included_line);
+            if lines.peek().is_none() && !included_line.ends_with('\n') {
+                expanded.push_str(&line[text.len()..]);
+            }
+        }
+    }
+    Ok(expanded)
+}
+
+/// `Some(Some(name))` for `@include name`, `Some(None)` for a bare directive.
+pub(crate) fn include_reference(entry: &str) -> Option<Option<&str>> {
+    let rest = entry.strip_prefix(INCLUDE_DIRECTIVE)?;
+    if !rest.is_empty() && !rest.starts_with(char::is_whitespace) {
+        return None;
+    }
+    let reference = rest.trim();
+    Some((!reference.is_empty()).then_some(reference))
+}
+
+/// Looks `reference` up in `category` first, then from the templates folder,
+/// taking each from the first source that has it.
+fn find_template(
+    template_dirs: &[PathBuf],
+    category: Option<&str>,
+    reference: &str,
+) -> Option<(TemplateId, String)> {
+    let (reference_category, name) = match reference.rsplit_once('/') {
+        Some((reference_category, name)) => (Some(reference_category), name),
+        None => (None, reference),
+    };
+    let mut candidates = Vec::with_capacity(2);
+    if let Some(category) = category {
+        candidates.push(Some(match reference_category {
+            Some(reference_category) => format!("{}/{}", category, reference_category),
+            None => category.to_string(),
+        }));
+    }
+    candidates.push(reference_category.map(str::to_string));
+    candidates.into_iter().find_map(|candidate| {
+        let content = template_dirs.iter().find_map(|dir| {
+            let path = templates::template_path(dir, candidate.as_deref(), name).ok()?;
+            fs::read_to_string(path).ok()
+        })?;
+        Some((
+            TemplateId {
+                category: candidate,
+                name: name.to_string(),
+            },
+            content,
+        ))
+    })
+}
+
+fn with_chain(message: String, chain: &[TemplateId]) -> String {
+    if chain.is_empty() {
+        return message;
+    }
+    let labels: Vec<String> = chain.iter().map(TemplateId::label).collect();
+    format!("{} (included from {})", message, labels.join(" → "))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::variables;
+    use std::collections::HashMap;
+
+    #[test]
+    fn included_declarations_are_merged_with_the_including_ones_first() {
+        let dir = tempfile::tempdir().unwrap();
+        let root = dir.path();
+        templates::save_template(
+            root,
+            None,
+            "Docs",
+            "---\nvariables:\n  author: Ada\n  title: Docs\n---\n{{title}}\n\t@include Tests",
+        )
+        .unwrap();
+        templates::save_template(
+            root,
+            None,
+            "Tests",
+            "---\nvariables:\n  framework:\n    options: [jest, vitest]\n    default: jest\n---\ntests_{{framework}}",
+        )
+        .unwrap();
+
+        let content = "---\nvariables:\n  title: Project\n---\n{{title}}\n\t@include Docs";
+        let expanded = expand_includes(&[root.to_path_buf()], content, None, None).unwrap();
+        let variables = variables::template_variables(&expanded).unwrap();
+        let declared: Vec<(&str, Option<&str>)> = variables
+            .iter()
+            .map(|variable| (variable.name.as_str(), variable.default.as_deref()))
+            .collect();
+        assert_eq!(
+            declared,
+            [
+                ("title", Some("Project")),
+                ("author", Some("Ada")),
+                ("framework", Some("jest")),
+            ]
+        );
+        let rendered = variables::render(&expanded, &HashMap::new()).unwrap();
+        assert!(rendered.ends_with("Project\n\tProject\n\t\ttests_jest"));
+    }
+
+    #[test]
+    fn content_without_new_declarations_keeps_its_frontmatter() {
+        let dir = tempfile::tempdir().unwrap();
+        let root = dir.path();
+        templates::save_template(root, None, "Plain", "plain").unwrap();
+        let content = "---\n# keep me\nvariables:\n  a: 1\n---\nroot\n\t@include Plain";
+        let expanded = expand_includes(&[root.to_path_buf()], content, None, None).unwrap();
+        assert_eq!(
+            expanded,
+            "---\n# keep me\nvariables:\n  a: 1\n---\nroot\n\tplain"
+        );
+    }
+
+    #[test]
+    fn sources_are_searched_in_order() {
+        let personal = tempfile::tempdir().unwrap();
+        let team = tempfile::tempdir().unwrap();
+        let dirs = [personal.path().to_path_buf(), team.path().to_path_buf()];
+        templates::save_template(team.path(), Some("Web"), "Docs", "team_docs").unwrap();
+        templates::save_template(team.path(), None, "Assets", "team_assets").unwrap();
+        templates::save_template(personal.path(), None, "Assets", "my_assets").unwrap();
+        templates::save_template(personal.path(), None, "Docs", "my_docs").unwrap();
+
+        let expanded = expand_includes(&dirs, "@include Assets", None, None).unwrap();
+        assert_eq!(expanded, "my_assets");
+        // The category is searched in every source before the top level.
+        let expanded = expand_includes(&dirs, "@include Docs", Some("Web"), None).unwrap();
+        assert_eq!(expanded, "team_docs");
+    }
+}
@@ -0,0 +1,406 @@
+// Undo journal for structure creation runs.
+//
+// Every run that changes the disk leaves `<history>/<run id>/journal.json`,
+// plus a `backups` folder holding the files it overwrote. Reverting a run walks
+// the journal backwards and only deletes entries that are unchanged since.
+
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+
+use crate::executor::{self, AppliedChange};
+use crate::metadata::millis_since_epoch;
+
+const JOURNAL_FILE: &str = "journal.json";
+const BACKUPS_DIR: &str = "backups";
+/// Skip reason for entries that are already gone; they are dropped from the
+/// journal instead of being retried.
+const NO_LONGER_EXISTS: &str = "No longer exists";
+
+// -----------------
+// Types
+// -----------------
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "camelCase")]
+pub enum JournalEntry {
+    #[serde(rename_all = "camelCase")]
+    Created {
+        path: String,
+        is_directory: bool,
+        /// Size and modification time (ms since epoch) right after creation,
+        /// used to tell whether the user has touched the file since.
+        size: Option<u64>,
+        modified: Option<u64>,
+    },
+    #[serde(rename_all = "camelCase")]
+    Linked { path: String, link_target: String },
+    #[serde(rename_all = "camelCase")]
+    Moved {
+        source_path: String,
+        target_path: String,
+    },
+    #[serde(rename_all = "camelCase")]
+    Overwritten {
+        path: String,
+        /// Relative to the run folder, so the history can move with the data
+        /// root. Older journals hold an absolute path, which is used as is.
+        backup_path: String,
+    },
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RunJournal {
+    pub id: String,
+    pub created_at: u64,
+    pub base_dir: String,
+    pub reverted: bool,
+    pub entries: Vec<JournalEntry>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RunSummary {
+    pub id: String,
+    pub created_at: u64,
+    pub base_dir: String,
+    pub reverted: bool,
+    pub created_count: usize,
+    pub moved_count: usize,
+    pub overwritten_count: usize,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SkippedEntry {
+    pub path: String,
+    pub reason: String,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RevertResult {
+    pub run_id: String,
+    pub reverted_count: usize,
+    pub skipped: Vec<SkippedEntry>,
+}
+
+// -----------------
+// Recording
+// -----------------
+fn now_millis() -> u64 {
+    millis_since_epoch(SystemTime::now()).unwrap_or(0)
+}
+
+fn modified_millis(metadata: &fs::Metadata) -> Option<u64> {
+    metadata.modified().ok().and_then(millis_since_epoch)
+}
+
+/// Writes the journal for a finished run and moves the backups of overwritten
+/// files into it. Returns the new run id. If the journal cannot be written the
+/// run folder is removed again, backups included, instead of being left behind
+/// without a journal that refers to them.
+pub(crate) fn record_run(
+    history_dir: &Path,
+    base_dir: &str,
+    applied: &[AppliedChange],
+) -> io::Result<String> {
+    let id = format!("{}-{}", now_millis(), uuid::Uuid::new_v4().simple());
+    let run_dir = history_dir.join(&id);
+    match write_run(&run_dir, &id, base_dir, applied) {
+        Ok(()) => Ok(id),
+        Err(error) => {
+            let _ = fs::remove_dir_all(&run_dir);
+            Err(error)
+        }
+    }
+}
+
+fn write_run(
+    run_dir: &Path,
+    id: &str,
+    base_dir: &str,
+    applied: &[AppliedChange],
+) -> io::Result<()> {
+    let backups_dir = run_dir.join(BACKUPS_DIR);
+    fs::create_dir_all(&backups_dir)?;
+
+    let mut entries = Vec::with_capacity(applied.len());
+    for (index, change) in applied.iter().enumerate() {
+        let entry = match change {
+            AppliedChange::CreatedFile(path) | AppliedChange::CreatedDirectory(path) => {
+                let metadata = fs::metadata(path).ok();
+                JournalEntry::Created {
+                    path: path.to_string_lossy().into_owned(),
+                    is_directory: matches!(change, AppliedChange::CreatedDirectory(_)),
+                    size: metadata.as_ref().filter(|m| m.is_file()).map(|m| m.len()),
+                    modified: metadata.as_ref().and_then(modified_millis),
+                }
+            }
+            AppliedChange::CreatedSymlink(path) => JournalEntry::Linked {
+                path: path.to_string_lossy().into_owned(),
+                link_target: fs::read_link(path)
+                    .map(|target| target.to_string_lossy().into_owned())
+                    .unwrap_or_default(),
+            },
+            AppliedChange::Moved { from, to } => JournalEntry::Moved {
+                source_path: from.to_string_lossy().into_owned(),
+                target_path: to.to_string_lossy().into_owned(),
+            },
+            AppliedChange::Replaced { target, backup } => {
+                let kept = Path::new(BACKUPS_DIR).join(index.to_string());
+                executor::move_path(backup, &run_dir.join(&kept))?;
+                JournalEntry::Overwritten {
+                    path: target.to_string_lossy().into_owned(),
+                    backup_path: kept.to_string_lossy().into_owned(),
+                }
+            }
+        };
+        entries.push(entry);
+    }
+
+    let journal = RunJournal {
+        id: id.to_string(),
+        created_at: now_millis(),
+        base_dir: base_dir.to_string(),
+        reverted: false,
+        entries,
+    };
+    write_journal(run_dir, &journal)
+}
+
+fn write_journal(run_dir: &Path, journal: &RunJournal) -> io::Result<()> {
+    let json = serde_json::to_string_pretty(journal)
+        .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+    fs::write(run_dir.join(JOURNAL_FILE), json)
+}
+
+fn read_journal(run_dir: &Path) -> Result<RunJournal, String> {
+    let json = fs::read_to_string(run_dir.join(JOURNAL_FILE)).map_err(|e| e.to_string())?;
+    serde_json::from_str(&json).map_err(|e| format!("Invalid journal {}: {}", run_dir.display(), e))
+}
+
+// -----------------
+// Listing and reverting
+// -----------------
+pub fn list_runs(history_dir: &Path) -> Result<Vec<RunSummary>, String> {
+    let mut runs = Vec::new();
+    if let Ok(entries) = fs::read_dir(history_dir) {
+        for entry in entries.flatten() {
+            let Ok(journal) = read_journal(&entry.path()) else {
+                continue;
+            };
+            let count = |predicate: fn(&JournalEntry) -> bool| {
+                journal.entries.iter().filter(|e| predicate(e)).count()
+            };
+            runs.push(RunSummary {
+                created_count: count(|e| {
+                    matches!(
+                        e,
+                        JournalEntry::Created { .. } | JournalEntry::Linked { .. }
+                    )
+                }),
+                moved_count: count(|e| matches!(e, JournalEntry::Moved { .. })),
+                overwritten_count: count(|e| matches!(e, JournalEntry::Overwritten { .. })),
+                id: journal.id,
+                created_at: journal.created_at,
+                base_dir: journal.base_dir,
+                reverted: journal.reverted,
+            });
+        }
+    }
+    runs.sort_by_key(|run| std::cmp::Reverse(run.created_at));
+    Ok(runs)
+}
+
+pub fn revert_run(history_dir: &Path, run_id: &str) -> Result<RevertResult, String> {
+    if run_id.is_empty() || run_id.contains(['/', '\\', '.']) {
+        return Err(format!("Invalid run id: {}", run_id));
+    }
+    let run_dir = history_dir.join(run_id);
+    let mut journal = read_journal(&run_dir)?;
+    if journal.reverted {
+        return Err(format!("Run {} has already been reverted", run_id));
+    }
+
+    let mut reverted_count = 0;
+    let mut skipped = Vec::new();
+    // Entries that could not be reverted stay in the journal, so the run can
+    // be reverted again once whatever blocked them is resolved.
+    let mut remaining = Vec::new();
+    for entry in journal.entries.into_iter().rev() {
+        match revert_entry(&run_dir, &entry) {
+            Ok(()) => reverted_count += 1,
+            Err((path, reason)) => {
+                if reason != NO_LONGER_EXISTS {
+                    remaining.push(entry);
+                }
+                skipped.push(SkippedEntry { path, reason });
+            }
+        }
+    }
+    remaining.reverse();
+
+    journal.reverted = remaining.is_empty();
+    journal.entries = remaining;
+    write_journal(&run_dir, &journal).map_err(|e| e.to_string())?;
+
+    Ok(RevertResult {
+        run_id: run_id.to_string(),
+        reverted_count,
+        skipped,
+    })
+}
+
+fn revert_entry(run_dir: &Path, entry: &JournalEntry) -> Result<(), (String, String)> {
+    match entry {
+        JournalEntry::Created {
+            path,
+            is_directory,
+            size,
+            modified,
+        } => {
+            let skip = |reason: String| (path.clone(), reason);
+            let current = Path::new(path);
+            let metadata = match fs::symlink_metadata(current) {
+                Ok(metadata) => metadata,
+                Err(_) => return Err(skip(NO_LONGER_EXISTS.to_string())),
+            };
+            if *is_directory {
+                // Only empty folders are removed; anything left inside was not
+                // created by this run or has been changed.
+                return fs::remove_dir(current)
+                    .map_err(|_| skip("Folder is not empty".to_string()));
+            }
+            let unchanged = metadata.is_file()
+                && Some(metadata.len()) == *size
+                && modified_millis(&metadata) == *modified;
+            if !unchanged {
+                return Err(skip("Modified since the run".to_string()));
+            }
+            fs::remove_file(current).map_err(|e| skip(e.to_string()))
+        }
+        JournalEntry::Linked { path, link_target } => {
+            let skip = |reason: String| (path.clone(), reason);
+            match fs::read_link(path) {
+                Ok(target) if target == Path::new(link_target) => {
+                    executor::remove_symlink(Path::new(path)).map_err(|e| skip(e.to_string()))
+                }
+                Err(_) if fs::symlink_metadata(path).is_err() => {
+                    Err(skip(NO_LONGER_EXISTS.to_string()))
+                }
+                _ => Err(skip("Modified since the run".to_string())),
+            }
+        }
+        JournalEntry::Moved {
+            source_path,
+            target_path,
+        } => {
+            let skip = |reason: String| (target_path.clone(), reason);
+            let (source, target) = (PathBuf::from(source_path), PathBuf::from(target_path));
+            if !target.exists() {
+                return Err(skip(NO_LONGER_EXISTS.to_string()));
+            }
+            if source.exists() {
+                return Err(skip(format!(
+                    "Original location is occupied: {}",
+                    source.display()
+                )));
+            }
+            if let Some(parent) = source.parent() {
+                fs::create_dir_all(parent).map_err(|e| skip(e.to_string()))?;
+            }
+            executor::move_path(&target, &source).map_err(|e| skip(e.to_string()))
+        }
+        JournalEntry::Overwritten { path, backup_path } => {
+            let skip = |reason: String| (path.clone(), reason);
+            if Path::new(path).exists() {
+                return Err(skip("Modified since the run".to_string()));
+            }
+            executor::move_path(&run_dir.join(backup_path), Path::new(path))
+                .map_err(|e| skip(e.to_string()))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::executor::ExecuteOptions;
+    use crate::structure;
+
+    fn run(base: &Path, history: &Path, text: &str) -> String {
+        let document = structure::parse(text).unwrap();
+        let operations = executor::operations_from_document(&document, base);
+        let options = ExecuteOptions {
+            history_dir: Some(history.to_path_buf()),
+            ..ExecuteOptions::default()
+        };
+        let result = executor::execute(&base.to_string_lossy(), &operations, &options);
+        assert_eq!(result.failure_count, 0, "{:?}", result.failures);
+        result.run_id.expect("run should be journaled")
+    }
+
+    #[test]
+    fn reverting_restores_moved_and_overwritten_files() {
+        let dir = tempfile::tempdir().unwrap();
+        let (base, history) = (dir.path().join("base"), dir.path().join("History"));
+        fs::create_dir(&base).unwrap();
+        fs::write(base.join("notes.md"), "notes").unwrap();
+        fs::write(base.join("README.md"), "original").unwrap();
+
+        let run_id = run(
+            &base,
+            &history,
+            "project\n\tsrc\n\t\tmain.rs\n\t(notes.md)\nREADME.md <<EOF\n\tnew\nEOF",
+        );
+        assert_eq!(fs::read_to_string(base.join("README.md")).unwrap(), "new\n");
+        let runs = list_runs(&history).unwrap();
+        assert_eq!(runs.len(), 1);
+        assert_eq!(runs[0].moved_count, 1);
+        assert_eq!(runs[0].overwritten_count, 1);
+
+        let result = revert_run(&history, &run_id).unwrap();
+
+        assert!(result.skipped.is_empty(), "{:?}", result.skipped);
+        assert!(!base.join("project").exists());
+        assert_eq!(fs::read_to_string(base.join("notes.md")).unwrap(), "notes");
+        assert_eq!(
+            fs::read_to_string(base.join("README.md")).unwrap(),
+            "original"
+        );
+        assert!(list_runs(&history).unwrap()[0].reverted);
+        assert!(revert_run(&history, &run_id).is_err());
+    }
+
+    #[test]
+    fn entries_that_could_not_be_reverted_are_kept_for_a_retry() {
+        let dir = tempfile::tempdir().unwrap();
+        let (base, history) = (dir.path().join("base"), dir.path().join("History"));
+        fs::create_dir(&base).unwrap();
+
+        let run_id = run(&base, &history, "project\n\tmain.rs\n\tlib.rs");
+        let main = base.join("project").join("main.rs");
+        fs::write(&main, "changed by hand").unwrap();
+
+        let result = revert_run(&history, &run_id).unwrap();
+
+        assert_eq!(result.reverted_count, 1);
+        let reasons: Vec<_> = result.skipped.iter().map(|s| s.reason.as_str()).collect();
+        assert_eq!(reasons, ["Modified since the run", "Folder is not empty"]);
+        assert!(main.is_file());
+        let journal = read_journal(&history.join(&run_id)).unwrap();
+        assert!(!journal.reverted);
+        assert_eq!(journal.entries.len(), 2);
+
+        // Once the changed file is gone, the retry finishes the revert.
+        fs::remove_file(&main).unwrap();
+        let result = revert_run(&history, &run_id).unwrap();
+
+        assert_eq!(result.reverted_count, 1);
+        assert!(!base.join("project").exists());
+        assert!(read_journal(&history.join(&run_id)).unwrap().reverted);
+    }
+}
@@ -0,0 +1,769 @@
+// Template linting: finds problems in a template before it is used, without
+// touching the file system beyond checking that copy sources exist.
+//
+// Every problem is reported with its line and a severity, instead of stopping
+// at the first one like the parser does. `@if` sections are all checked; two
+// entries in different branches of the same `@if` never clash.
+
+use serde::{Deserialize, Serialize};
+use std::path::PathBuf;
+
+use crate::conditions::{self, Directive};
+use crate::includes;
+use crate::structure::{self, NodeKind, Span, StructureNode};
+use crate::templates;
+use crate::variables;
+
+/// Longest path Windows accepts unless long paths are enabled.
+const WINDOWS_MAX_PATH: usize = 260;
+/// Longest file name, in bytes, on common file systems.
+const MAX_NAME_BYTES: usize = 255;
+const WINDOWS_FORBIDDEN: [char; 8] = ['<', '>', ':', '"', '|', '?', '*', '\\'];
+
+// -----------------
+// Types
+// -----------------
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub enum Severity {
+    Error,
+    Warning,
+    Info,
+}
+
+/// Platforms whose file name rules are checked; named like the `os` condition.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum TargetOs {
+    Windows,
+    Macos,
+    Linux,
+}
+
+impl TargetOs {
+    const ALL: [TargetOs; 3] = [TargetOs::Windows, TargetOs::Macos, TargetOs::Linux];
+
+    fn label(self) -> &'static str {
+        match self {
+            TargetOs::Windows => "Windows",
+            TargetOs::Macos => "macOS",
+            TargetOs::Linux => "Linux",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct LintOptions {
+    /// Destination the template will be created in. Relative copy sources and
+    /// full path lengths are only checked when it is known.
+    pub base_dir: Option<String>,
+    /// Platforms to check names for; all of them when empty.
+    pub targets: Vec<TargetOs>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Diagnostic {
+    pub severity: Severity,
+    /// Stable identifier of the check, e.g. `duplicateName`.
+    pub code: &'static str,
+    pub message: String,
+    pub span: Span,
+}
+
+impl Diagnostic {
+    fn new(severity: Severity, code: &'static str, message: impl Into<String>, span: Span) -> Self {
+        Diagnostic {
+            severity,
+            code,
+            message: message.into(),
+            span,
+        }
+    }
+}
+
+/// The `@if` branches a line sits in, outermost first, as `(section, in_else)`.
+type Branches = Vec<(usize, bool)>;
+
+/// The body with directive lines blanked out, so it parses as a plain
+/// structure with unchanged line numbers.
+struct Preprocessed {
+    text: String,
+    /// Number of frontmatter lines before the body.
+    body_start: usize,
+    /// Branches of each line of the whole text, by 0-based index.
+    branches: Vec<Branches>,
+    /// Whether each line is inside a content block.
+    in_content: Vec<bool>,
+}
+
+// -----------------
+// Linting
+// -----------------
+
+/// Lints `content`, resolving includes in `template_dirs` in order. `category`
+/// and `name` identify the template being linted, if it is one.
+pub fn lint(
+    template_dirs: &[PathBuf],
+    content: &str,
+    category: Option<&str>,
+    name: Option<&str>,
+    options: &LintOptions,
+) -> Vec<Diagnostic> {
+    let mut diagnostics = Vec::new();
+    let (frontmatter, body) = match structure::split_frontmatter(content) {
+        Ok(split) => split,
+        Err(error) => {
+            diagnostics.push(Diagnostic::new(
+                Severity::Error,
+                "frontmatter",
+                error.message,
+                error.span,
+            ));
+            return diagnostics;
+        }
+    };
+    if let Some(frontmatter) = &frontmatter {
+        for error in templates::parse_metadata(content).1 {
+            diagnostics.push(Diagnostic::new(
+                Severity::Warning,
+                "frontmatter",
+                error,
+                frontmatter.span,
+            ));
+        }
+    }
+
+    let header = &content[..content.len() - body.len()];
+    let mut preprocessed = preprocess(
+        template_dirs,
+        header,
+        body,
+        category,
+        name,
+        &mut diagnostics,
+    );
+    check_variables(content, body, &preprocessed, &mut diagnostics);
+    check_indentation(&mut preprocessed, &mut diagnostics);
+
+    match structure::parse(&preprocessed.text) {
+        Ok(document) => {
+            let targets = if options.targets.is_empty() {
+                TargetOs::ALL.to_vec()
+            } else {
+                options.targets.clone()
+            };
+            let checker = NodeChecker {
+                branches: &preprocessed.branches,
+                base_dir: options.base_dir.as_deref().map(crate::expand_tilde_path),
+                targets,
+            };
+            checker.check(&document.nodes, "", false, &mut diagnostics);
+        }
+        Err(error) => diagnostics.push(Diagnostic::new(
+            Severity::Error,
+            "syntax",
+            error.message,
+            error.span,
+        )),
+    }
+
+    diagnostics.sort_by_key(|diagnostic| {
+        (
+            diagnostic.span.line,
+            diagnostic.span.column,
+            diagnostic.severity,
+        )
+    });
+    diagnostics
+}
+
+fn line_span(line: usize, text: &str) -> Span {
+    Span {
+        line,
+        column: 1,
+        end_line: line,
+        end_column: text.chars().count() + 1,
+    }
+}
+
+/// Checks `@if`/`@include` directives and blanks them out.
+fn preprocess(
+    template_dirs: &[PathBuf],
+    header: &str,
+    body: &str,
+    category: Option<&str>,
+    name: Option<&str>,
+    diagnostics: &mut Vec<Diagnostic>,
+) -> Preprocessed {
+    let header_lines = header.matches('\n').count();
+    let mut text = header.to_string();
+    let mut branches = vec![Branches::new(); header_lines];
+    let mut in_content = vec![false; header_lines];
+
+    // Open sections as (id, in_else, line).
+    let mut sections: Vec<(usize, bool, usize)> = Vec::new();
+    let mut next_section = 0;
+    let mut open_block: Option<String> = None;
+
+    for (index, raw) in body.split('\n').enumerate() {
+        let line_number = header_lines + index + 1;
+        let line = raw.strip_suffix('\r').unwrap_or(raw);
+        if index > 0 {
+            text.push('\n');
+        }
+        branches.push(
+            sections
+                .iter()
+                .map(|&(id, in_else, _)| (id, in_else))
+                .collect(),
+        );
+
+        if let Some(delimiter) = &open_block {
+            if line.trim_start_matches('\t') == delimiter {
+                open_block = None;
+            }
+            in_content.push(true);
+            text.push_str(raw);
+            continue;
+        }
+        in_content.push(false);
+
+        let entry = line.trim_start_matches('\t').trim_end();
+        let problem = |message: String| {
+            Diagnostic::new(
+                Severity::Error,
+                "directive",
+                message,
+                line_span(line_number, line),
+            )
+        };
+        match conditions::directive(line) {
+            Some(Directive::If(condition)) => {
+                if let Err(error) = conditions::check_condition(condition) {
+                    diagnostics.push(problem(error));
+                }
+                sections.push((next_section, false, line_number));
+                next_section += 1;
+                continue;
+            }
+            Some(Directive::Else) => {
+                match sections.last_mut() {
+                    Some(section) if !section.1 => section.1 = true,
+                    Some(_) => diagnostics.push(problem("Duplicate '@else'".to_string())),
+                    None => diagnostics.push(problem("'@else' without '@if'".to_string())),
+                }
+                continue;
+            }
+            Some(Directive::End) => {
+                if sections.pop().is_none() {
+                    diagnostics.push(problem("'@endif' without '@if'".to_string()));
+                }
+                continue;
+            }
+            None => {}
+        }
+        if includes::include_reference(entry).is_some() {
+            if let Err(error) = includes::expand_includes(template_dirs, entry, category, name) {
+                diagnostics.push(Diagnostic::new(
+                    Severity::Error,
+                    "include",
+                    error,
+                    line_span(line_number, line),
+                ));
+            }
+            continue;
+        }
+
+        open_block = structure::content_delimiter(line).map(str::to_string);
+        text.push_str(raw);
+    }
+
+    for (_, _, line) in sections {
+        diagnostics.push(Diagnostic::new(
+            Severity::Error,
+            "directive",
+            "'@if' is not closed with '@endif'",
+            Span {
+                line,
+                column: 1,
+                end_line: line,
+                end_column: 1,
+            },
+        ));
+    }
+
+    Preprocessed {
+        text,
+        body_start: header_lines,
+        branches,
+        in_content,
+    }
+}
+
+/// Reports placeholders that are not declared in the frontmatter: they are
+/// left as written when the template is used.
+fn check_variables(
+    content: &str,
+    body: &str,
+    preprocessed: &Preprocessed,
+    diagnostics: &mut Vec<Diagnostic>,
+) {
+    let Ok(known) = variables::template_variables(content) else {
+        return;
+    };
+    let declared = |name: &str| {
+        known
+            .iter()
+            .any(|variable| variable.declared && variable.name == name)
+    };
+    for (index, line) in body.split('\n').enumerate() {
+        let line_number = preprocessed.body_start + index + 1;
+        let in_content = preprocessed.in_content[line_number - 1];
+        for (start, name, end) in variables::placeholders(line) {
+            if declared(name) {
+                continue;
+            }
+            // File content may use `{{ }}` for its own purposes.
+            let (severity, message) = if in_content {
+                (
+                    Severity::Info,
+                    format!("'{{{{{}}}}}' in file content is not a declared variable and is kept as written", name),
+                )
+            } else {
+                (
+                    Severity::Warning,
+                    format!("Variable '{}' is not declared in the frontmatter", name),
+                )
+            };
+            let column = line[..start].chars().count() + 1;
+            diagnostics.push(Diagnostic::new(
+                severity,
+                "undeclaredVariable",
+                message,
+                Span {
+                    line: line_number,
+                    column,
+                    end_line: line_number,
+                    end_column: column + line[start..end].chars().count(),
+                },
+            ));
+        }
+    }
+}
+
+/// Reports every space indentation and indentation jump, which the parser
+/// would stop at, and fixes them in the text so the entries can still be
+/// checked: spaces are dropped and jumps are pulled back one level below the
+/// previous entry.
+fn check_indentation(preprocessed: &mut Preprocessed, diagnostics: &mut Vec<Diagnostic>) {
+    let mut previous_level: Option<usize> = None;
+    let mut lines: Vec<String> = Vec::new();
+    for (index, raw) in preprocessed.text.split('\n').enumerate() {
+        let line_number = index + 1;
+        let line = raw.trim_end();
+        if index < preprocessed.body_start
+            || line.trim().is_empty()
+            || preprocessed.in_content[index]
+        {
+            lines.push(raw.to_string());
+            continue;
+        }
+
+        let mut level = line.chars().take_while(|c| *c == '\t').count();
+        let rest = &line[level..];
+        if rest.starts_with(' ') {
+            diagnostics.push(Diagnostic::new(
+                Severity::Error,
+                "indentation",
+                "Indentation must use tabs",
+                line_span(line_number, line),
+            ));
+        }
+        let allowed = previous_level.map_or(0, |previous| previous + 1);
+        if level > allowed {
+            let message = match previous_level {
+                None => "The first entry cannot be indented".to_string(),
+                Some(previous) => format!(
+                    "Indentation jumps from level {} to level {}",
+                    previous, level
+                ),
+            };
+            diagnostics.push(Diagnostic::new(
+                Severity::Error,
+                "indentation",
+                message,
+                Span {
+                    line: line_number,
+                    column: 1,
+                    end_line: line_number,
+                    end_column: level + 1,
+                },
+            ));
+            level = allowed;
+        }
+        previous_level = Some(level);
+        lines.push(format!(
+            "{}{}",
+            "\t".repeat(level),
+            rest.trim_start_matches(' ')
+        ));
+    }
+    preprocessed.text = lines.join("\n");
+}
+
+// -----------------
+// Entries
+// -----------------
+struct NodeChecker<'a> {
+    branches: &'a [Branches],
+    base_dir: Option<PathBuf>,
+    targets: Vec<TargetOs>,
+}
+
+impl NodeChecker<'_> {
+    /// Checks `nodes` and their children. `parent` is the path of their folder
+    /// relative to the destination; `too_long` is set once a path has already
+    /// been reported as too long, so its children are not.
+    fn check(
+        &self,
+        nodes: &[StructureNode],
+        parent: &str,
+        too_long: bool,
+        diagnostics: &mut Vec<Diagnostic>,
+    ) {
+        self.check_siblings(nodes, diagnostics);
+        for node in nodes {
+            // The longest of the repeated names.
+            let name = match node.repeat {
+                Some(count) => structure::repeated_name(&node.name, count),
+                None => node.name.clone(),
+            };
+            self.check_name(node, &name, diagnostics);
+            if matches!(node.kind, NodeKind::Copy | NodeKind::Move) {
+                self.check_source(node, diagnostics);
+            }
+
+            let path = if parent.is_empty() {
+                name
+            } else {
+                format!("{}/{}", parent, name)
+            };
+            let reported = too_long || self.check_path_length(node, &path, diagnostics);
+            self.check(&node.children, &path, reported, diagnostics);
+        }
+    }
+
+    /// Whether two lines are in different branches of the same `@if`.
+    fn exclusive(&self, a: usize, b: usize) -> bool {
+        let (Some(a), Some(b)) = (self.branches.get(a - 1), self.branches.get(b - 1)) else {
+            return false;
+        };
+        a.iter().any(|(section, in_else)| {
+            b.iter()
+                .any(|(other, other_else)| section == other && in_else != other_else)
+        })
+    }
+
+    fn check_siblings(&self, nodes: &[StructureNode], diagnostics: &mut Vec<Diagnostic>) {
+        let names: Vec<Vec<String>> = nodes
+            .iter()
+            .map(|node| match node.repeat {
+                Some(count) => (1..=count)
+                    .map(|index| structure::repeated_name(&node.name, index))
+                    .collect(),
+                None => vec![node.name.clone()],
+            })
+            .collect();
+
+        for (index, node) in nodes.iter().enumerate() {
+            for (earlier_index, earlier) in nodes[..index].iter().enumerate() {
+                if self.exclusive(earlier.span.line, node.span.line) {
+                    continue;
+                }
+                let clash = names[index].iter().find_map(|name| {
+                    names[earlier_index]
+                        .iter()
+                        .find(|other| other.to_lowercase() == name.to_lowercase())
+                        .map(|other| (name, other))
+                });
+                let Some((name, other)) = clash else {
+                    continue;
+                };
+                let diagnostic = if name == other {
+                    Diagnostic::new(
+                        Severity::Error,
+                        "duplicateName",
+                        format!("'{}' is already used on line {}", name, earlier.span.line),
+                        node.name_span,
+                    )
+                } else {
+                    Diagnostic::new(
+                        Severity::Warning,
+                        "caseCollision",
+                        format!(
+                            "'{}' and '{}' on line {} are the same name on case-insensitive file systems such as macOS and Windows",
+                            name, other, earlier.span.line
+                        ),
+                        node.name_span,
+                    )
+                };
+                diagnostics.push(diagnostic);
+                break;
+            }
+        }
+    }
+
+    fn check_name(&self, node: &StructureNode, name: &str, diagnostics: &mut Vec<Diagnostic>) {
+        let name = without_placeholders(name);
+        if name.len() > MAX_NAME_BYTES {
+            diagnostics.push(Diagnostic::new(
+                Severity::Error,
+                "nameTooLong",
+                format!(
+                    "Name is {} bytes long; most file systems allow at most {}",
+                    name.len(),
+                    MAX_NAME_BYTES
+                ),
+                node.name_span,
+            ));
+        }
+
+        // Platforms grouped by the reason the name is invalid there.
+        let mut problems: Vec<(String, Vec<&str>)> = Vec::new();
+        for target in &self.targets {
+            if let Some(reason) = invalid_name_reason(&name, *target) {
+                match problems.iter_mut().find(|(known, _)| *known == reason) {
+                    Some((_, platforms)) => platforms.push(target.label()),
+                    None => problems.push((reason, vec![target.label()])),
+                }
+            }
+        }
+        for (reason, platforms) in problems {
+            diagnostics.push(Diagnostic::new(
+                Severity::Error,
+                "invalidName",
+                format!(
+                    "'{}' is not a valid name on {}: {}",
+                    name,
+                    platforms.join(" and "),
+                    reason
+                ),
+                node.name_span,
+            ));
+        }
+    }
+
+    fn check_source(&self, node: &StructureNode, diagnostics: &mut Vec<Diagnostic>) {
+        let Some(source) = node.source.as_deref() else {
+            return;
+        };
+        if !variables::placeholders(source).is_empty() {
+            return;
+        }
+        let expanded = crate::expand_tilde_path(source);
+        let path = if expanded.is_absolute() {
+            expanded
+        } else {
+            match &self.base_dir {
+                Some(base_dir) => base_dir.join(expanded),
+                None => return,
+            }
+        };
+        if !path.exists() {
+            diagnostics.push(Diagnostic::new(
+                Severity::Warning,
+                "missingSource",
+                format!("Source does not exist: {}", path.display()),
+                node.source_span.unwrap_or(node.span),
+            ));
+        }
+    }
+
+    /// Reports a path longer than Windows allows; true if it did.
+    fn check_path_length(
+        &self,
+        node: &StructureNode,
+        path: &str,
+        diagnostics: &mut Vec<Diagnostic>,
+    ) -> bool {
+        if !self.targets.contains(&TargetOs::Windows) {
+            return false;
+        }
+        let full = match &self.base_dir {
+            Some(base_dir) => base_dir.join(path).to_string_lossy().into_owned(),
+            None => path.to_string(),
+        };
+        let length = full.chars().count();
+        if length <= WINDOWS_MAX_PATH {
+            return false;
+        }
+        diagnostics.push(Diagnostic::new(
+            Severity::Warning,
+            "pathTooLong",
+            format!(
+                "Path is {} characters long; Windows allows {} unless long paths are enabled",
+                length, WINDOWS_MAX_PATH
+            ),
+            node.name_span,
+        ));
+        true
+    }
+}
+
+/// Replaces each `{{name}}` with its name, since the value is unknown.
+fn without_placeholders(name: &str) -> String {
+    let mut plain = String::with_capacity(name.len());
+    let mut from = 0;
+    for (start, variable, end) in variables::placeholders(name) {
+        plain.push_str(&name[from..start]);
+        plain.push_str(variable);
+        from = end;
+    }
+    plain.push_str(&name[from..]);
+    plain
+}
+
+fn invalid_name_reason(name: &str, target: TargetOs) -> Option<String> {
+    if let Some(control) = name.chars().find(|c| c.is_control()) {
+        if target == TargetOs::Windows || control == '\0' {
+            return Some("it contains a control character".to_string());
+        }
+    }
+    match target {
+        TargetOs::Windows => {
+            if let Some(forbidden) = name.chars().find(|c| WINDOWS_FORBIDDEN.contains(c)) {
+                return Some(format!("it contains '{}'", forbidden));
+            }
+            if name.ends_with(['.', ' ']) {
+                return Some("it ends with a dot or a space".to_string());
+            }
+            let stem = name.split('.').next().unwrap_or(name).trim_end();
+            templates::RESERVED_NAMES
+                .iter()
+                .find(|reserved| reserved.eq_ignore_ascii_case(stem))
+                .map(|reserved| format!("'{}' is a reserved device name", reserved))
+        }
+        TargetOs::Macos => name.contains(':').then(|| "it contains ':'".to_string()),
+        TargetOs::Linux => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn lint_with(content: &str, targets: &[TargetOs]) -> Vec<Diagnostic> {
+        let options = LintOptions {
+            base_dir: None,
+            targets: targets.to_vec(),
+        };
+        lint(&[], content, None, None, &options)
+    }
+
+    fn codes(diagnostics: &[Diagnostic]) -> Vec<(usize, &'static str, Severity)> {
+        diagnostics
+            .iter()
+            .map(|diagnostic| (diagnostic.span.line, diagnostic.code, diagnostic.severity))
+            .collect()
+    }
+
+    #[test]
+    fn names_clash_unless_in_different_branches_of_the_same_if() {
+        let content = "project\n\tREADME.md\n\t@if docs\n\treadme.md\n\t@else\n\tREADME.md\n\t@endif\n\t@if unix\n\tsetup.sh\n\t@else\n\tsetup.sh\n\t@if ci\n\tSetup.sh\n\t@endif\n\t@endif";
+
+        let diagnostics = lint_with(content, &[]);
+
+        assert_eq!(
+            codes(&diagnostics),
+            [
+                (4, "caseCollision", Severity::Warning),
+                (6, "duplicateName", Severity::Error),
+                (13, "caseCollision", Severity::Warning),
+            ]
+        );
+        assert_eq!(
+            diagnostics[1].message,
+            "'README.md' is already used on line 2"
+        );
+        assert!(diagnostics[2]
+            .message
+            .starts_with("'Setup.sh' and 'setup.sh' on line 11"));
+    }
+
+    #[test]
+    fn indentation_problems_are_reported_and_the_rest_is_still_checked() {
+        let content = "project\n\t\tsrc\n\t\tmain.rs\n\t\tmain.rs\n  notes.md\n\t\t\tlib.rs";
+
+        let diagnostics = lint_with(content, &[]);
+
+        assert_eq!(
+            codes(&diagnostics),
+            [
+                (2, "indentation", Severity::Error),
+                (4, "duplicateName", Severity::Error),
+                (5, "indentation", Severity::Error),
+                (6, "indentation", Severity::Error),
+            ]
+        );
+        assert_eq!(
+            diagnostics[0].message,
+            "Indentation jumps from level 0 to level 2"
+        );
+        assert_eq!(diagnostics[2].message, "Indentation must use tabs");
+        assert_eq!(
+            diagnostics[3].message,
+            "Indentation jumps from level 0 to level 3"
+        );
+        assert_eq!(
+            lint_with("\tproject", &[])[0].message,
+            "The first entry cannot be indented"
+        );
+    }
+
+    #[test]
+    fn names_are_checked_for_every_target_platform() {
+        let content = "project\n\tcon.txt\n\ta:b.txt\n\tnotes.\n\tdraft?.md\n\tok.txt";
+
+        let messages: Vec<String> = lint_with(content, &[])
+            .into_iter()
+            .map(|diagnostic| diagnostic.message)
+            .collect();
+
+        assert_eq!(
+            messages,
+            [
+                "'con.txt' is not a valid name on Windows: 'CON' is a reserved device name",
+                "'a:b.txt' is not a valid name on Windows and macOS: it contains ':'",
+                "'notes.' is not a valid name on Windows: it ends with a dot or a space",
+                "'draft?.md' is not a valid name on Windows: it contains '?'",
+            ]
+        );
+        assert!(lint_with(content, &[TargetOs::Linux]).is_empty());
+        assert_eq!(lint_with(content, &[TargetOs::Macos]).len(), 1);
+    }
+
+    #[test]
+    fn undeclared_variables_are_warnings_in_names_and_notes_in_content() {
+        let content = "---\nvariables:\n  project:\n    default: app\n---\n{{project}}\n\t{{module}}.rs\n\tconfig.json <<EOF\n\t\t{\"name\": \"{{project}}\", \"env\": \"{{env}}\"}\n\tEOF";
+
+        let diagnostics = lint_with(content, &[]);
+
+        assert_eq!(
+            codes(&diagnostics),
+            [
+                (7, "undeclaredVariable", Severity::Warning),
+                (9, "undeclaredVariable", Severity::Info),
+            ]
+        );
+        assert_eq!(
+            diagnostics[0].message,
+            "Variable 'module' is not declared in the frontmatter"
+        );
+        assert_eq!(
+            (diagnostics[1].span.column, diagnostics[1].span.end_column),
+            (35, 42)
+        );
+    }
+}
@@ -1,21 +1,44 @@
 #![cfg_attr(not(debug_assertions), windows_subsystem = "windows")]
 
+// -----------------
+// Modules
+// -----------------
+mod builtins;
+mod conditions;
+mod executor;
+mod extract;
+mod filter;
+mod includes;
+mod journal;
+mod lint;
+mod metadata;
+mod packs;
+mod planner;
+mod revisions;
+mod scan;
+mod storage;
+mod structure;
+mod templates;
+mod variables;
+mod watcher;
+
 // -----------------
 // Imports
 // -----------------
 use dirs;
 use dotenv::dotenv;
-use serde::{Deserialize, Serialize};
+use serde::Serialize;
+use std::collections::HashMap;
 use std::fs;
-use std::io;
 #[cfg(target_os = "macos")]
 use std::num::NonZeroU32;
 use std::path::{Path, PathBuf};
-use tauri::{Runtime, Emitter};
+use std::sync::Mutex;
+use tauri::{Runtime, Emitter, Manager};
 #[cfg(target_os = "macos")]
 use tauri::menu::{Menu, MenuItem, PredefinedMenuItem, Submenu};
 use tauri_plugin_deep_link::DeepLinkExt;
-use zip::ZipArchive;
+use tauri_plugin_fs::FsExt;
 
 #[cfg(target_os = "macos")]
 use core_foundation::{
@@ -123,28 +146,42 @@ graphic-design-project
 			slides
 			presentation_01.pdf"#;
 
-const DEFAULT_TEMPLATES: [(&str, &str); 4] = [
-    ("Web Project", WEB_PROJECT_TEMPLATE),
-    ("School Class", SCHOOL_CLASS_TEMPLATE),
-    ("Video Project", VIDEO_PROJECT_TEMPLATE),
-    ("Graphic Design Project", GRAPHIC_DESIGN_TEMPLATE),
+// Bump `version` when changing a template so unmodified copies can be updated.
+const DEFAULT_TEMPLATES: [builtins::BuiltinTemplate; 4] = [
+    builtins::BuiltinTemplate {
+        name: "Web Project",
+        version: 1,
+        content: WEB_PROJECT_TEMPLATE,
+    },
+    builtins::BuiltinTemplate {
+        name: "School Class",
+        version: 1,
+        content: SCHOOL_CLASS_TEMPLATE,
+    },
+    builtins::BuiltinTemplate {
+        name: "Video Project",
+        version: 1,
+        content: VIDEO_PROJECT_TEMPLATE,
+    },
+    builtins::BuiltinTemplate {
+        name: "Graphic Design Project",
+        version: 1,
+        content: GRAPHIC_DESIGN_TEMPLATE,
+    },
 ];
-const DEFAULTS_SENTINEL_FILE: &str = ".defaults_initialized";
 
 // -----------------
 // Types
 // -----------------
-#[derive(Serialize, Deserialize)]
-struct Template {
-    name: String,
-    content: String,
-}
-
 #[derive(Serialize)]
 struct DirectoryEntry {
     name: String,
     #[serde(rename = "isDirectory")]
     is_directory: bool,
+    #[serde(rename = "isSymlink")]
+    is_symlink: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    metadata: Option<metadata::EntryMetadata>,
 }
 
 #[derive(Serialize)]
@@ -154,6 +191,10 @@ struct FileInfo {
     exists: bool,
     #[serde(rename = "isDirectory")]
     is_directory: bool,
+    #[serde(rename = "isSymlink")]
+    is_symlink: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    metadata: Option<metadata::EntryMetadata>,
 }
 
 // -----------------
@@ -213,7 +254,11 @@ fn check_file_exists(path: String) -> bool {
 }
 
 #[tauri::command]
-fn read_directory_structure(path: String) -> Result<Vec<DirectoryEntry>, String> {
+fn read_directory_structure(
+    path: String,
+    options: Option<filter::FilterOptions>,
+    include_metadata: Option<bool>,
+) -> Result<Vec<DirectoryEntry>, String> {
     let path = Path::new(&path);
     if !path.exists() {
         return Err(format!("Path does not exist: {}", path.display()));
@@ -223,19 +268,34 @@ fn read_directory_structure(path: String) -> Result<Vec<DirectoryEntry>, String>
     }
 
     let mut contents = Vec::new();
+    let filter = filter::EntryFilter::new(path, &options.unwrap_or_default())?;
 
     // Read directory entries
     let entries = fs::read_dir(&path).map_err(|e| e.to_string())?;
     for entry in entries {
         if let Ok(entry) = entry {
             let name = entry.file_name().to_string_lossy().into_owned();
-            // Skip hidden files and directories (starting with a dot)
-            if !name.starts_with('.') {
-                contents.push(DirectoryEntry {
-                    name,
-                    is_directory: entry.file_type().map_err(|e| e.to_string())?.is_dir(),
-                });
+            let file_type = entry.file_type().map_err(|e| e.to_string())?;
+            let is_symlink = file_type.is_symlink();
+            // Linked folders can still be browsed into.
+            let is_directory = file_type.is_dir() || (is_symlink && entry.path().is_dir());
+            // Skip hidden, ignored and excluded entries
+            if filter.is_excluded(&entry.path(), &name, is_directory)
+                || (!is_directory && !filter.is_included(&name, &name))
+            {
+                continue;
             }
+            let metadata = if include_metadata.unwrap_or(false) {
+                metadata::read_entry_metadata(&entry.path(), false).ok()
+            } else {
+                None
+            };
+            contents.push(DirectoryEntry {
+                name,
+                is_directory,
+                is_symlink,
+                metadata,
+            });
         }
     }
 
@@ -343,7 +403,11 @@ fn get_hardware_uuid() -> Result<String, String> {
 }
 
 #[tauri::command]
-fn read_directory_contents(path: String) -> Result<Vec<FileInfo>, String> {
+fn read_directory_contents(
+    path: String,
+    options: Option<filter::FilterOptions>,
+    include_metadata: Option<bool>,
+) -> Result<Vec<FileInfo>, String> {
     let path = Path::new(&path);
     if !path.exists() {
         return Err(format!("Path does not exist: {}", path.display()));
@@ -353,19 +417,34 @@ fn read_directory_contents(path: String) -> Result<Vec<FileInfo>, String> {
     }
 
     let mut contents = Vec::new();
+    let filter = filter::EntryFilter::new(path, &options.unwrap_or_default())?;
     let entries = fs::read_dir(&path).map_err(|e| e.to_string())?;
 
     for entry in entries {
         if let Ok(entry) = entry {
             let name = entry.file_name().to_string_lossy().into_owned();
-            if !name.starts_with('.') {
-                contents.push(FileInfo {
-                    name,
-                    indent: 0,
-                    exists: true,
-                    is_directory: entry.file_type().map_err(|e| e.to_string())?.is_dir(),
-                });
+            let file_type = entry.file_type().map_err(|e| e.to_string())?;
+            let is_symlink = file_type.is_symlink();
+            // Linked folders can still be browsed into.
+            let is_directory = file_type.is_dir() || (is_symlink && entry.path().is_dir());
+            if filter.is_excluded(&entry.path(), &name, is_directory)
+                || (!is_directory && !filter.is_included(&name, &name))
+            {
+                continue;
             }
+            let metadata = if include_metadata.unwrap_or(false) {
+                metadata::read_entry_metadata(&entry.path(), false).ok()
+            } else {
+                None
+            };
+            contents.push(FileInfo {
+                name,
+                indent: 0,
+                exists: true,
+                is_directory,
+                is_symlink,
+                metadata,
+            });
         }
     }
 
@@ -380,6 +459,41 @@ fn read_directory_contents(path: String) -> Result<Vec<FileInfo>, String> {
     Ok(contents)
 }
 
+/// Runs blocking file work on the blocking thread pool, so async commands do
+/// not hold up the async workers while they walk or copy folders.
+async fn run_blocking<T: Send + 'static>(
+    work: impl FnOnce() -> Result<T, String> + Send + 'static,
+) -> Result<T, String> {
+    tauri::async_runtime::spawn_blocking(work)
+        .await
+        .map_err(|e| e.to_string())?
+}
+
+/// Metadata for a single path, e.g. a `[copy]` source. With `total_size`,
+/// folders report the combined size of their contents.
+#[tauri::command]
+async fn get_path_metadata(
+    path: String,
+    total_size: Option<bool>,
+) -> Result<metadata::EntryMetadata, String> {
+    run_blocking(move || {
+        metadata::read_entry_metadata(&expand_tilde_path(&path), total_size.unwrap_or(false))
+    })
+    .await
+}
+
+/// Walks a folder and returns structure text that recreates it.
+#[tauri::command]
+async fn scan_to_structure(
+    path: String,
+    options: Option<scan::ScanOptions>,
+) -> Result<scan::ScannedStructure, String> {
+    run_blocking(move || {
+        scan::scan_to_structure(&expand_tilde_path(&path), &options.unwrap_or_default())
+    })
+    .await
+}
+
 #[tauri::command]
 fn remove_file(path: String) -> Result<(), String> {
     fs::remove_file(path).map_err(|e| e.to_string())
@@ -405,94 +519,544 @@ async fn remove_path(path: &str, recursive: bool) -> Result<(), String> {
     Ok(())
 }
 
+// -----------------
+// Structure Parsing
+// -----------------
+#[tauri::command]
+fn parse_structure(content: String) -> Result<structure::StructureDocument, structure::ParseError> {
+    structure::parse(&content)
+}
+
+// -----------------
+// Structure Creation
+// -----------------
+fn get_blank_files_dir(workspace: &storage::Workspace) -> Option<PathBuf> {
+    storage::data_root(workspace)
+        .ok()
+        .map(|root| root.join(storage::BLANK_FILES_FOLDER))
+}
+
+fn get_history_dir(workspace: &storage::Workspace) -> Result<PathBuf, String> {
+    let path = storage::data_root(workspace)?.join(storage::HISTORY_FOLDER);
+    fs::create_dir_all(&path).map_err(|e| e.to_string())?;
+    Ok(path)
+}
+
+/// Resolves command input to operations: either the raw structure text or
+/// operations already computed by the frontend.
+/// Targets outside `base_dir` are rejected.
+fn resolve_operations(
+    workspace: &storage::Workspace,
+    base_dir: &str,
+    structure: Option<String>,
+    operations: Option<Vec<executor::Operation>>,
+    variables: Option<HashMap<String, String>>,
+) -> Result<Vec<executor::Operation>, String> {
+    let base_dir = expand_tilde_path(base_dir);
+    let operations = match (operations, structure) {
+        (Some(operations), _) => operations,
+        (None, Some(structure)) => {
+            let structure = includes::expand_includes(&storage::template_dirs(workspace)?, &structure, None, None)?;
+            let structure = variables::render(&structure, &variables.unwrap_or_default())?;
+            let document = structure::parse(&structure).map_err(|e| e.to_string())?;
+            executor::operations_from_document(&document, &base_dir)
+        }
+        (None, None) => return Err("Either a structure or operations must be provided".to_string()),
+    };
+    executor::check_targets(&base_dir, &operations)?;
+    Ok(operations)
+}
+
+/// Lists the `{{variables}}` a structure needs, so the UI can prompt for them.
+#[tauri::command]
+fn get_template_variables(
+    workspace: tauri::State<'_, storage::Workspace>,
+    content: String,
+) -> Result<Vec<variables::TemplateVariable>, String> {
+    let content = includes::expand_includes(&storage::template_dirs(&workspace)?, &content, None, None)?;
+    variables::template_variables(&content)
+}
+
+/// Replaces `@include` lines with the bodies of the templates they name.
+#[tauri::command]
+fn expand_template_includes(
+    workspace: tauri::State<'_, storage::Workspace>,
+    content: String,
+    name: Option<String>,
+    category: Option<String>,
+) -> Result<String, String> {
+    includes::expand_includes(
+        &storage::template_dirs(&workspace)?,
+        &content,
+        category.as_deref(),
+        name.as_deref(),
+    )
+}
+
+/// Checks a template for problems such as indentation jumps, invalid or
+/// duplicate names, undeclared variables, missing copy sources and overly long
+/// paths, without creating anything.
+#[tauri::command]
+fn lint_template(
+    workspace: tauri::State<'_, storage::Workspace>,
+    content: String,
+    name: Option<String>,
+    category: Option<String>,
+    options: Option<lint::LintOptions>,
+) -> Result<Vec<lint::Diagnostic>, String> {
+    Ok(lint::lint(
+        &storage::template_dirs(&workspace)?,
+        &content,
+        category.as_deref(),
+        name.as_deref(),
+        &options.unwrap_or_default(),
+    ))
+}
+
+/// Expands includes and substitutes variable values (or their defaults) into a
+/// structure, the same way `create_structure` does before running it.
+#[tauri::command]
+fn render_structure(
+    workspace: tauri::State<'_, storage::Workspace>,
+    content: String,
+    values: HashMap<String, String>,
+) -> Result<String, String> {
+    let content = includes::expand_includes(&storage::template_dirs(&workspace)?, &content, None, None)?;
+    variables::render(&content, &values)
+}
+
+/// Reports what a run would do, classifying each target without touching disk.
+#[tauri::command]
+async fn plan_structure(
+    workspace: tauri::State<'_, storage::Workspace>,
+    base_dir: String,
+    structure: Option<String>,
+    operations: Option<Vec<executor::Operation>>,
+    variables: Option<HashMap<String, String>>,
+    options: Option<executor::ExecuteOptions>,
+) -> Result<planner::StructurePlan, String> {
+    let operations = resolve_operations(&workspace, &base_dir, structure, operations, variables)?;
+    let mut options = options.unwrap_or_default();
+    options.blank_files_dir = get_blank_files_dir(&workspace);
+
+    run_blocking(move || Ok(planner::plan(&base_dir, &operations, &options))).await
+}
+
+/// Runs every operation of a structure in one call.
+#[tauri::command]
+async fn create_structure(
+    workspace: tauri::State<'_, storage::Workspace>,
+    base_dir: String,
+    structure: Option<String>,
+    operations: Option<Vec<executor::Operation>>,
+    variables: Option<HashMap<String, String>>,
+    options: Option<executor::ExecuteOptions>,
+) -> Result<executor::ExecutionResult, String> {
+    let operations = resolve_operations(&workspace, &base_dir, structure, operations, variables)?;
+    let mut options = options.unwrap_or_default();
+    options.blank_files_dir = get_blank_files_dir(&workspace);
+    options.history_dir = Some(get_history_dir(&workspace)?);
+
+    run_blocking(move || Ok(executor::execute(&base_dir, &operations, &options))).await
+}
+
+#[tauri::command]
+fn list_creation_runs(
+    workspace: tauri::State<'_, storage::Workspace>,
+) -> Result<Vec<journal::RunSummary>, String> {
+    journal::list_runs(&get_history_dir(&workspace)?)
+}
+
+/// Undoes a past run: moved entries go back to where they came from and
+/// created entries are deleted only when unchanged since the run.
+#[tauri::command]
+async fn revert_creation_run(
+    workspace: tauri::State<'_, storage::Workspace>,
+    run_id: String,
+) -> Result<journal::RevertResult, String> {
+    let history_dir = get_history_dir(&workspace)?;
+    run_blocking(move || journal::revert_run(&history_dir, &run_id)).await
+}
+
 // -----------------
 // Template Management
 // -----------------
-fn get_templates_dir() -> Result<PathBuf, String> {
-    let mut path = dirs::document_dir()
-        .ok_or_else(|| "Could not find documents directory".to_string())?;
-    path.push("FileArchitect");
-    path.push("Templates");
+fn get_templates_dir(workspace: &storage::Workspace) -> Result<PathBuf, String> {
+    let path = storage::data_root(workspace)?.join(storage::TEMPLATES_FOLDER);
     fs::create_dir_all(&path).map_err(|e| e.to_string())?;
     Ok(path)
 }
 
-fn ensure_default_templates() -> Result<(), String> {
-    let templates_dir = get_templates_dir()?;
+/// Installs built-in templates that were never installed. Deleted ones are
+/// not brought back; see `restore_builtin_template`.
+fn ensure_default_templates(workspace: &storage::Workspace) -> Result<(), String> {
+    builtins::sync(&get_templates_dir(workspace)?, &DEFAULT_TEMPLATES)
+}
 
-    // Respect user deletions: if the sentinel file exists we have already seeded once
-    let sentinel_path = templates_dir.join(DEFAULTS_SENTINEL_FILE);
-    if sentinel_path.exists() {
-        return Ok(());
-    }
+/// Seeds the templates at the current data root and points the templates
+/// watcher and the frontend's file access at it. The home folder and the
+/// filesystem root are refused rather than opened up entirely.
+fn use_data_root<R: Runtime>(app: &tauri::AppHandle<R>) -> Result<storage::DataLocation, String> {
+    let workspace = app.state::<storage::Workspace>();
+    let location = storage::location(&workspace)?;
+    storage::check_data_root(Path::new(&location.root))?;
+    ensure_default_templates(&workspace)?;
+    let _ = app.fs_scope().allow_directory(&location.root, true);
+    watch_templates(app)?;
+    Ok(location)
+}
 
-    // Create a HashSet of existing template names for quick lookup
-    let existing_templates: std::collections::HashSet<String> = if let Ok(entries) = fs::read_dir(&templates_dir) {
-        entries
-            .filter_map(|entry| entry.ok())
-            .filter_map(|entry| {
-                entry.path()
-                    .file_stem()
-                    .and_then(|s| s.to_str())
-                    .map(|s| s.to_string())
-            })
-            .collect()
-    } else {
-        std::collections::HashSet::new()
+/// Points the templates watcher at the current template sources. A folder
+/// that cannot be watched is reported to the frontend, which then no longer
+/// hears about outside changes.
+fn watch_templates<R: Runtime>(app: &tauri::AppHandle<R>) -> Result<(), String> {
+    let Some(state) = app.try_state::<Mutex<Option<watcher::TemplatesWatcher>>>() else {
+        return Ok(());
     };
-
-    // Write each default template if it doesn't exist
-    for (name, content) in DEFAULT_TEMPLATES.iter() {
-        if !existing_templates.contains(*name) {
-            let file_path = templates_dir.join(format!("{}.txt", name));
-            fs::write(&file_path, content).map_err(|e| e.to_string())?;
+    let mut templates_watcher = state.lock().map_err(|e| e.to_string())?;
+    // Stop watching the old folders before watching the new ones
+    *templates_watcher = None;
+    let template_dirs = storage::template_dirs(&app.state::<storage::Workspace>())?;
+    *templates_watcher = match watcher::watch(app.clone(), template_dirs) {
+        Ok(templates_watcher) => Some(templates_watcher),
+        Err(error) => {
+            let _ = app.emit(
+                watcher::WATCH_FAILED_EVENT,
+                format!("Could not watch the templates folders: {}", error),
+            );
+            None
         }
-    }
+    };
+    Ok(())
+}
 
-    // Create the sentinel file so we don't reseed every start-up
-    fs::write(&sentinel_path, "").map_err(|e| e.to_string())?;
+/// Returns the data root, where it was configured, and the folders in it.
+#[tauri::command]
+fn get_data_location(
+    workspace: tauri::State<'_, storage::Workspace>,
+) -> Result<storage::DataLocation, String> {
+    storage::location(&workspace)
+}
 
-    Ok(())
+/// Sets the data root used when neither `FILEARCHITECT_DATA_DIR` nor the
+/// workspace overrides it, or resets it with `None`. Existing data stays where
+/// it is; use `migrate_data_root` to move it.
+#[tauri::command]
+fn set_data_root<R: Runtime>(
+    app: tauri::AppHandle<R>,
+    path: Option<String>,
+) -> Result<storage::DataLocation, String> {
+    storage::set_configured_root(path.as_deref())?;
+    use_data_root(&app)
+}
+
+/// Sets the folder whose `.filearchitect.json` can override the data root.
+#[tauri::command]
+fn set_workspace<R: Runtime>(
+    app: tauri::AppHandle<R>,
+    path: Option<String>,
+) -> Result<storage::DataLocation, String> {
+    app.state::<storage::Workspace>().set(path.as_deref())?;
+    use_data_root(&app)
+}
+
+/// Moves templates, blank files and history to `destination` and uses it as
+/// the data root from now on.
+#[tauri::command]
+async fn migrate_data_root<R: Runtime>(
+    app: tauri::AppHandle<R>,
+    destination: String,
+) -> Result<storage::DataLocation, String> {
+    run_blocking(move || {
+        if let Some(state) = app.try_state::<Mutex<Option<watcher::TemplatesWatcher>>>() {
+            *state.lock().map_err(|e| e.to_string())? = None;
+        }
+        let migrated = storage::migrate(&app.state::<storage::Workspace>(), &destination);
+        let location = use_data_root(&app)?;
+        migrated.map(|_| location)
+    })
+    .await
 }
 
 #[tauri::command]
-async fn initialize_app() -> Result<(), String> {
+async fn initialize_app(workspace: tauri::State<'_, storage::Workspace>) -> Result<(), String> {
     // Ensure templates are initialized before the app starts
-    ensure_default_templates()?;
+    ensure_default_templates(&workspace)?;
     Ok(())
 }
 
+/// Reports each built-in template as up to date, updatable, modified by the
+/// user or deleted.
 #[tauri::command]
-fn get_templates() -> Result<Vec<Template>, String> {
-    let templates_dir = get_templates_dir()?;
-    let mut templates = Vec::new();
-    
-    if let Ok(entries) = fs::read_dir(&templates_dir) {
-        for entry in entries {
-            if let Ok(entry) = entry {
-                let path = entry.path();
-                if path.extension().map_or(false, |ext| ext == "txt") {
-                    if let Ok(content) = fs::read_to_string(&path) {
-                        let name = path.file_stem()
-                            .and_then(|s| s.to_str())
-                            .unwrap_or("Unnamed Template")
-                            .to_string();
-                        templates.push(Template { name, content });
-                    }
-                }
-            }
+fn list_builtin_templates(
+    workspace: tauri::State<'_, storage::Workspace>,
+) -> Result<Vec<builtins::BuiltinTemplateInfo>, String> {
+    builtins::list(&get_templates_dir(&workspace)?, &DEFAULT_TEMPLATES)
+}
+
+/// Updates the given built-ins, or all of them, unless the user edited them.
+#[tauri::command]
+fn apply_builtin_template_updates(
+    workspace: tauri::State<'_, storage::Workspace>,
+    names: Option<Vec<String>>,
+) -> Result<Vec<String>, String> {
+    builtins::apply_updates(&get_templates_dir(&workspace)?, &DEFAULT_TEMPLATES, names.as_deref())
+}
+
+#[tauri::command]
+fn restore_builtin_template(
+    workspace: tauri::State<'_, storage::Workspace>,
+    name: String,
+) -> Result<(), String> {
+    builtins::restore(&get_templates_dir(&workspace)?, &DEFAULT_TEMPLATES, &name)
+}
+
+/// Lists the templates of every source with their parsed frontmatter. Files
+/// that cannot be read are reported in `errors` instead of being skipped.
+#[tauri::command]
+fn get_templates(
+    workspace: tauri::State<'_, storage::Workspace>,
+) -> Result<templates::TemplateListing, String> {
+    get_templates_dir(&workspace)?;
+    Ok(templates::load_sources(&storage::template_sources(&workspace)?))
+}
+
+/// Saves into the personal templates, or into `source` unless it is read-only.
+#[tauri::command]
+fn save_template(
+    workspace: tauri::State<'_, storage::Workspace>,
+    name: String,
+    content: String,
+    category: Option<String>,
+    source: Option<String>,
+) -> Result<(), String> {
+    let dir = writable_templates_dir(&workspace, source.as_deref())?;
+    templates::save_template(&dir, category.as_deref(), &name, &content)
+}
+
+/// Folder of the template source named `source`, the personal templates when
+/// none is given. Commands that change templates refuse read-only sources.
+fn writable_templates_dir(
+    workspace: &storage::Workspace,
+    source: Option<&str>,
+) -> Result<PathBuf, String> {
+    match source {
+        Some(source) if source != templates::PERSONAL_SOURCE => {
+            storage::writable_template_source(workspace, source)
         }
+        _ => get_templates_dir(workspace),
     }
-    
-    Ok(templates)
 }
 
+/// Like `writable_templates_dir`, for commands that only read templates.
+fn readable_templates_dir(
+    workspace: &storage::Workspace,
+    source: Option<&str>,
+) -> Result<PathBuf, String> {
+    match source {
+        Some(source) if source != templates::PERSONAL_SOURCE => {
+            storage::template_source_dir(workspace, source)
+        }
+        _ => get_templates_dir(workspace),
+    }
+}
+
+/// Lists the personal templates followed by the configured sources, in order
+/// of precedence.
 #[tauri::command]
-fn save_template(name: String, content: String) -> Result<(), String> {
-    let templates_dir = get_templates_dir()?;
-    let file_path = templates_dir.join(format!("{}.txt", name));
-    fs::write(&file_path, content).map_err(|e| e.to_string())?;
-    Ok(())
+fn get_template_sources(
+    workspace: tauri::State<'_, storage::Workspace>,
+) -> Result<Vec<templates::TemplateSource>, String> {
+    storage::template_sources(&workspace)
+}
+
+/// Replaces the sources read after the personal templates.
+#[tauri::command]
+fn set_template_sources<R: Runtime>(
+    app: tauri::AppHandle<R>,
+    sources: Vec<templates::TemplateSource>,
+) -> Result<(), String> {
+    storage::set_template_sources(sources)?;
+    watch_templates(&app)
+}
+
+#[tauri::command]
+fn rename_template(
+    workspace: tauri::State<'_, storage::Workspace>,
+    name: String,
+    new_name: String,
+    category: Option<String>,
+    source: Option<String>,
+) -> Result<(), String> {
+    templates::rename_template(
+        &writable_templates_dir(&workspace, source.as_deref())?,
+        category.as_deref(),
+        &name,
+        &new_name,
+    )
+}
+
+/// Copies a template in its category and returns the copy's name.
+#[tauri::command]
+fn duplicate_template(
+    workspace: tauri::State<'_, storage::Workspace>,
+    name: String,
+    new_name: Option<String>,
+    category: Option<String>,
+    source: Option<String>,
+) -> Result<String, String> {
+    templates::duplicate_template(
+        &writable_templates_dir(&workspace, source.as_deref())?,
+        category.as_deref(),
+        &name,
+        new_name.as_deref(),
+    )
+}
+
+/// Previous versions of a template, newest first.
+#[tauri::command]
+fn list_template_revisions(
+    workspace: tauri::State<'_, storage::Workspace>,
+    name: String,
+    category: Option<String>,
+    source: Option<String>,
+) -> Result<Vec<revisions::TemplateRevision>, String> {
+    revisions::list_revisions(
+        &readable_templates_dir(&workspace, source.as_deref())?,
+        category.as_deref(),
+        &name,
+    )
+}
+
+/// Line diff between two revisions; pass `current` for the saved template.
+#[tauri::command]
+fn diff_template_revisions(
+    workspace: tauri::State<'_, storage::Workspace>,
+    name: String,
+    from: String,
+    to: String,
+    category: Option<String>,
+    source: Option<String>,
+) -> Result<Vec<revisions::DiffLine>, String> {
+    revisions::diff_revisions(
+        &readable_templates_dir(&workspace, source.as_deref())?,
+        category.as_deref(),
+        &name,
+        &from,
+        &to,
+    )
+}
+
+#[tauri::command]
+fn restore_template_revision(
+    workspace: tauri::State<'_, storage::Workspace>,
+    name: String,
+    revision_id: String,
+    category: Option<String>,
+    source: Option<String>,
+) -> Result<(), String> {
+    revisions::restore_revision(
+        &writable_templates_dir(&workspace, source.as_deref())?,
+        category.as_deref(),
+        &name,
+        &revision_id,
+    )
+}
+
+/// Deletes a template and its revision history.
+#[tauri::command]
+fn delete_template(
+    workspace: tauri::State<'_, storage::Workspace>,
+    name: String,
+    category: Option<String>,
+    source: Option<String>,
+) -> Result<(), String> {
+    templates::delete_template(
+        &writable_templates_dir(&workspace, source.as_deref())?,
+        category.as_deref(),
+        &name,
+    )
+}
+
+/// Creates a category folder; `path` may be nested, e.g. `Work/Clients`.
+#[tauri::command]
+fn create_template_category(
+    workspace: tauri::State<'_, storage::Workspace>,
+    path: String,
+    source: Option<String>,
+) -> Result<(), String> {
+    templates::create_category(&writable_templates_dir(&workspace, source.as_deref())?, &path)
+}
+
+#[tauri::command]
+fn rename_template_category(
+    workspace: tauri::State<'_, storage::Workspace>,
+    path: String,
+    new_name: String,
+    source: Option<String>,
+) -> Result<(), String> {
+    templates::rename_category(&writable_templates_dir(&workspace, source.as_deref())?, &path, &new_name)
+}
+
+#[tauri::command]
+fn move_template(
+    workspace: tauri::State<'_, storage::Workspace>,
+    name: String,
+    category: Option<String>,
+    target_category: Option<String>,
+    source: Option<String>,
+) -> Result<(), String> {
+    templates::move_template(
+        &writable_templates_dir(&workspace, source.as_deref())?,
+        &name,
+        category.as_deref(),
+        target_category.as_deref(),
+    )
+}
+
+/// Bundles templates, and the blank files they use, into a `.fatemplates` pack.
+#[tauri::command]
+async fn export_templates(
+    workspace: tauri::State<'_, storage::Workspace>,
+    templates: Vec<templates::TemplateRef>,
+    destination_path: String,
+) -> Result<packs::ExportResult, String> {
+    let template_dirs = storage::template_dirs(&workspace)?;
+    let blank_files_dir = get_blank_files_dir(&workspace);
+    run_blocking(move || {
+        packs::export_templates(
+            &template_dirs,
+            blank_files_dir.as_deref(),
+            &templates,
+            &expand_tilde_path(&destination_path),
+        )
+    })
+    .await
+}
+
+/// Reads a pack's manifest so the UI can show what an import would add.
+#[tauri::command]
+fn read_template_pack(path: String) -> Result<packs::PackManifest, String> {
+    packs::read_manifest(&expand_tilde_path(&path))
+}
+
+#[tauri::command]
+async fn import_templates(
+    workspace: tauri::State<'_, storage::Workspace>,
+    path: String,
+    conflict_policy: Option<packs::ImportConflictPolicy>,
+) -> Result<packs::ImportResult, String> {
+    let templates_dir = get_templates_dir(&workspace)?;
+    let blank_files_dir = get_blank_files_dir(&workspace);
+    run_blocking(move || {
+        packs::import_templates(
+            &templates_dir,
+            blank_files_dir.as_deref(),
+            &expand_tilde_path(&path),
+            conflict_policy.unwrap_or_default(),
+        )
+    })
+    .await
 }
 
 #[tauri::command]
@@ -502,54 +1066,43 @@ async fn handle_deep_link<R: Runtime>(app_handle: tauri::AppHandle<R>, url: Stri
     Ok(())
 }
 
+/// Extracts a downloaded ZIP archive, emitting `extract-progress` events.
+/// Entries that would end up outside `destination_path` are skipped and listed
+/// in the summary. Pass an `extraction_id` to be able to cancel it.
 #[tauri::command]
-fn extract_zip(zip_path: String, destination_path: String) -> Result<(), String> {
-    println!("Extracting ZIP: {} to {}", zip_path, destination_path);
-    
-    // Open the zip file
-    let file = fs::File::open(&zip_path)
-        .map_err(|e| format!("Failed to open zip file: {}", e))?;
-    
-    // Parse the zip
-    let mut archive = ZipArchive::new(file)
-        .map_err(|e| format!("Failed to parse zip: {}", e))?;
-    
-    // Create destination directory if it doesn't exist
-    fs::create_dir_all(&destination_path)
-        .map_err(|e| format!("Failed to create destination directory: {}", e))?;
-    
-    // Extract each file
-    for i in 0..archive.len() {
-        let mut file = archive.by_index(i)
-            .map_err(|e| format!("Failed to access zip entry {}: {}", i, e))?;
-        
-        let outpath = Path::new(&destination_path).join(file.name());
-        
-        // Create directory if needed
-        if file.name().ends_with('/') {
-            fs::create_dir_all(&outpath)
-                .map_err(|e| format!("Failed to create directory {}: {}", outpath.display(), e))?;
-            continue;
-        }
-        
-        // Create parent directory if needed
-        if let Some(parent) = outpath.parent() {
-            if !parent.exists() {
-                fs::create_dir_all(parent)
-                    .map_err(|e| format!("Failed to create directory {}: {}", parent.display(), e))?;
-            }
-        }
-        
-        // Extract file
-        let mut outfile = fs::File::create(&outpath)
-            .map_err(|e| format!("Failed to create file {}: {}", outpath.display(), e))?;
-        
-        io::copy(&mut file, &mut outfile)
-            .map_err(|e| format!("Failed to write to file {}: {}", outpath.display(), e))?;
-    }
-    
-    println!("ZIP extraction complete");
-    Ok(())
+async fn extract_zip<R: Runtime>(
+    app: tauri::AppHandle<R>,
+    extractions: tauri::State<'_, extract::Extractions>,
+    zip_path: String,
+    destination_path: String,
+    extraction_id: Option<String>,
+) -> Result<extract::ExtractSummary, String> {
+    let id = extraction_id.unwrap_or_else(|| uuid::Uuid::new_v4().to_string());
+    let extraction = extractions.start(id.clone())?;
+    // Extraction is blocking file I/O; keep it off the async workers.
+    let result = tauri::async_runtime::spawn_blocking(move || {
+        extract::extract_zip(
+            Path::new(&zip_path),
+            Path::new(&destination_path),
+            &extraction,
+            &mut |progress| {
+                let _ = app.emit(extract::EXTRACT_PROGRESS_EVENT, progress);
+            },
+        )
+    })
+    .await;
+    extractions.finish(&id);
+    result.map_err(|e| format!("Extraction failed: {}", e))?
+}
+
+/// Stops a running extraction; whatever it extracted so far is removed.
+/// Returns false when no extraction has this id.
+#[tauri::command]
+fn cancel_extraction(
+    extractions: tauri::State<'_, extract::Extractions>,
+    extraction_id: String,
+) -> bool {
+    extractions.cancel(&extraction_id)
 }
 
 // -----------------
@@ -569,9 +1122,13 @@ fn main() {
         .plugin(tauri_plugin_http::init())
         .plugin(tauri_plugin_deep_link::init())
         .plugin(tauri_plugin_os::init())
+        .manage(extract::Extractions::default())
+        .manage(storage::Workspace::default())
         .setup(|app| {
-            ensure_default_templates()?;
-            
+            // The watcher tells the frontend about templates edited or synced outside the app
+            app.manage(Mutex::new(None::<watcher::TemplatesWatcher>));
+            use_data_root(app.handle())?;
+
             // Register deep link handler only on supported platforms (macOS uses config-only; runtime registration not supported)
             #[cfg(any(target_os = "linux", all(debug_assertions, windows)))]
             {
@@ -669,13 +1226,46 @@ fn main() {
             reveal_file_command,
             get_hardware_uuid,
             read_directory_contents,
+            get_path_metadata,
+            scan_to_structure,
             remove_file,
             remove_path,
             get_templates,
             save_template,
+            get_template_sources,
+            set_template_sources,
+            rename_template,
+            duplicate_template,
+            delete_template,
+            list_template_revisions,
+            diff_template_revisions,
+            restore_template_revision,
+            export_templates,
+            read_template_pack,
+            import_templates,
+            create_template_category,
+            rename_template_category,
+            move_template,
+            list_builtin_templates,
+            apply_builtin_template_updates,
+            restore_builtin_template,
+            get_data_location,
+            set_data_root,
+            set_workspace,
+            migrate_data_root,
             initialize_app,
             handle_deep_link,
             extract_zip,
+            cancel_extraction,
+            parse_structure,
+            get_template_variables,
+            render_structure,
+            lint_template,
+            expand_template_includes,
+            plan_structure,
+            create_structure,
+            list_creation_runs,
+            revert_creation_run,
         ])
         .run(tauri::generate_context!())
         .expect("error while running tauri application");
@@ -0,0 +1,100 @@
+// Optional filesystem metadata attached to listing results, so the preview can
+// show sizes, dates and links before anything is overwritten or copied.
+
+use serde::Serialize;
+use std::fs;
+use std::path::Path;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct EntryMetadata {
+    /// File size in bytes; for folders, the total size when requested.
+    pub size: Option<u64>,
+    /// Milliseconds since the Unix epoch.
+    pub modified: Option<u64>,
+    pub created: Option<u64>,
+    pub is_symlink: bool,
+    pub symlink_target: Option<String>,
+    pub readonly: bool,
+    /// Octal permission bits such as `755`; not available on Windows.
+    pub permissions: Option<String>,
+    /// Number of direct children, for folders.
+    pub child_count: Option<usize>,
+}
+
+pub fn millis_since_epoch(time: SystemTime) -> Option<u64> {
+    time.duration_since(UNIX_EPOCH)
+        .ok()
+        .map(|duration| duration.as_millis() as u64)
+}
+
+/// Reads the metadata of `path` without failing on broken links. With
+/// `total_size`, folders report the size of everything below them.
+pub fn read_entry_metadata(path: &Path, total_size: bool) -> Result<EntryMetadata, String> {
+    let link_metadata = fs::symlink_metadata(path).map_err(|e| e.to_string())?;
+    let is_symlink = link_metadata.file_type().is_symlink();
+    // Describe what a link points to, falling back to the link itself when broken.
+    let metadata = if is_symlink {
+        fs::metadata(path).unwrap_or(link_metadata)
+    } else {
+        link_metadata
+    };
+
+    let (size, child_count) = if metadata.is_dir() {
+        let child_count = fs::read_dir(path).ok().map(|entries| entries.count());
+        let size = if total_size {
+            Some(directory_size(path))
+        } else {
+            None
+        };
+        (size, child_count)
+    } else {
+        (Some(metadata.len()), None)
+    };
+
+    Ok(EntryMetadata {
+        size,
+        modified: metadata.modified().ok().and_then(millis_since_epoch),
+        created: metadata.created().ok().and_then(millis_since_epoch),
+        is_symlink,
+        symlink_target: if is_symlink {
+            fs::read_link(path)
+                .ok()
+                .map(|target| target.to_string_lossy().into_owned())
+        } else {
+            None
+        },
+        readonly: metadata.permissions().readonly(),
+        permissions: permission_bits(&metadata),
+        child_count,
+    })
+}
+
+#[cfg(unix)]
+fn permission_bits(metadata: &fs::Metadata) -> Option<String> {
+    use std::os::unix::fs::PermissionsExt;
+    Some(format!("{:o}", metadata.permissions().mode() & 0o777))
+}
+
+#[cfg(not(unix))]
+fn permission_bits(_metadata: &fs::Metadata) -> Option<String> {
+    None
+}
+
+/// Total size of the files below `path`. Links are not followed.
+fn directory_size(path: &Path) -> u64 {
+    let Ok(entries) = fs::read_dir(path) else {
+        return 0;
+    };
+    entries
+        .flatten()
+        .map(|entry| match entry.file_type() {
+            Ok(file_type) if file_type.is_dir() => directory_size(&entry.path()),
+            Ok(file_type) if file_type.is_file() => {
+                entry.metadata().map(|metadata| metadata.len()).unwrap_or(0)
+            }
+            _ => 0,
+        })
+        .sum()
+}
@@ -0,0 +1,499 @@
+// Template packs: several templates, and the blank files they rely on, in one
+// `.fatemplates` zip archive.
+//
+//   manifest.json
+//   templates/<category>/<name>.txt
+//   blank-files/blank.<ext>
+
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeSet;
+use std::fs;
+use std::io::{Read, Write};
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+use zip::write::FileOptions;
+use zip::{ZipArchive, ZipWriter};
+
+use crate::conditions;
+use crate::includes;
+use crate::metadata::millis_since_epoch;
+use crate::structure::{self, NodeKind, StructureNode};
+use crate::templates::{self, TemplateRef};
+use crate::variables;
+
+pub const PACK_EXTENSION: &str = "fatemplates";
+const PACK_FORMAT: &str = "fatemplates";
+const PACK_VERSION: u32 = 1;
+const MANIFEST_FILE: &str = "manifest.json";
+const TEMPLATES_DIR: &str = "templates";
+const BLANK_FILES_DIR: &str = "blank-files";
+/// Upper bound for a single template or blank file read from a pack.
+const MAX_ENTRY_SIZE: u64 = 16 * 1024 * 1024;
+
+// -----------------
+// Types
+// -----------------
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PackManifest {
+    pub format: String,
+    pub version: u32,
+    pub created_at: u64,
+    pub templates: Vec<PackTemplate>,
+    /// Extensions of the blank files included, e.g. `docx`.
+    #[serde(default)]
+    pub blank_files: Vec<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PackTemplate {
+    pub name: String,
+    pub category: Option<String>,
+    /// Location of the template inside the archive.
+    pub path: String,
+    #[serde(default)]
+    pub description: Option<String>,
+    #[serde(default)]
+    pub version: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ExportResult {
+    pub path: String,
+    /// Selected templates plus the templates they include.
+    pub template_count: usize,
+    pub blank_file_count: usize,
+    /// Templates whose blank files could not be worked out, with the reason.
+    pub warnings: Vec<String>,
+}
+
+/// What to do with a template that already exists when importing.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum ImportConflictPolicy {
+    #[default]
+    Skip,
+    Overwrite,
+    /// Import under a new name such as `Web Project (2)`.
+    KeepBoth,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub enum ImportStatus {
+    Imported,
+    Overwritten,
+    Renamed,
+    Skipped,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ImportedTemplate {
+    pub name: String,
+    pub category: Option<String>,
+    pub status: ImportStatus,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ImportResult {
+    pub templates: Vec<ImportedTemplate>,
+    pub blank_file_count: usize,
+}
+
+// -----------------
+// Export
+// -----------------
+
+/// Packs the selected templates, looked up in `template_dirs` in order of
+/// precedence, along with every template they `@include` so the pack works
+/// on its own.
+pub fn export_templates(
+    template_dirs: &[PathBuf],
+    blank_files_dir: Option<&Path>,
+    selection: &[TemplateRef],
+    destination: &Path,
+) -> Result<ExportResult, String> {
+    if selection.is_empty() {
+        return Err("No templates selected".to_string());
+    }
+    let destination = if destination
+        .extension()
+        .is_some_and(|ext| ext == PACK_EXTENSION)
+    {
+        destination.to_path_buf()
+    } else {
+        let mut file_name = destination.as_os_str().to_owned();
+        file_name.push(format!(".{}", PACK_EXTENSION));
+        PathBuf::from(file_name)
+    };
+
+    let mut manifest = PackManifest {
+        format: PACK_FORMAT.to_string(),
+        version: PACK_VERSION,
+        created_at: millis_since_epoch(SystemTime::now()).unwrap_or(0),
+        templates: Vec::with_capacity(selection.len()),
+        blank_files: Vec::new(),
+    };
+    let mut packed: Vec<(Option<String>, String, String)> = Vec::new();
+    for selected in selection {
+        let category = selected.category.clone().filter(|c| !c.is_empty());
+        let content = read_template(template_dirs, category.as_deref(), &selected.name)?;
+        let included = includes::included_templates(template_dirs, &content, category.as_deref())
+            .map_err(|e| format!("Template '{}': {}", selected.name, e))?;
+        for template in std::iter::once((category, selected.name.clone(), content)).chain(included)
+        {
+            if !packed
+                .iter()
+                .any(|(category, name, _)| (category, name) == (&template.0, &template.1))
+            {
+                packed.push(template);
+            }
+        }
+    }
+
+    let mut contents = Vec::with_capacity(packed.len());
+    let mut extensions = BTreeSet::new();
+    let mut warnings = Vec::new();
+    for (category, name, content) in packed {
+        if let Err(error) = template_extensions(&content, &mut extensions) {
+            warnings.push(format!(
+                "Blank files used by '{}' are not included: {}",
+                name, error
+            ));
+        }
+        let archive_path = match &category {
+            Some(category) => format!("{}/{}/{}.txt", TEMPLATES_DIR, category, name),
+            None => format!("{}/{}.txt", TEMPLATES_DIR, name),
+        };
+        let template = templates::load_template(name, category, content);
+        manifest.templates.push(PackTemplate {
+            name: template.name,
+            category: template.category,
+            path: archive_path.clone(),
+            description: template.metadata.description,
+            version: template.metadata.version,
+        });
+        contents.push((archive_path, template.content));
+    }
+
+    let blank_files: Vec<(String, PathBuf)> = blank_files_dir
+        .map(|dir| {
+            extensions
+                .into_iter()
+                .map(|extension| {
+                    let path = dir.join(format!("blank.{}", extension));
+                    (extension, path)
+                })
+                .filter(|(_, path)| path.is_file())
+                .collect()
+        })
+        .unwrap_or_default();
+    manifest.blank_files = blank_files.iter().map(|(ext, _)| ext.clone()).collect();
+
+    let write = || -> Result<(), String> {
+        let file = fs::File::create(&destination)
+            .map_err(|e| format!("Failed to create {}: {}", destination.display(), e))?;
+        let mut zip = ZipWriter::new(file);
+        let options = FileOptions::default();
+        let manifest_json = serde_json::to_string_pretty(&manifest).map_err(|e| e.to_string())?;
+        zip.start_file(MANIFEST_FILE, options)
+            .map_err(|e| e.to_string())?;
+        zip.write_all(manifest_json.as_bytes())
+            .map_err(|e| e.to_string())?;
+        for (archive_path, content) in &contents {
+            zip.start_file(archive_path.as_str(), options)
+                .map_err(|e| e.to_string())?;
+            zip.write_all(content.as_bytes())
+                .map_err(|e| e.to_string())?;
+        }
+        for (extension, path) in &blank_files {
+            let bytes = fs::read(path).map_err(|e| e.to_string())?;
+            zip.start_file(format!("{}/blank.{}", BLANK_FILES_DIR, extension), options)
+                .map_err(|e| e.to_string())?;
+            zip.write_all(&bytes).map_err(|e| e.to_string())?;
+        }
+        zip.finish().map_err(|e| e.to_string())?;
+        Ok(())
+    };
+    if let Err(error) = write() {
+        let _ = fs::remove_file(&destination);
+        return Err(error);
+    }
+
+    Ok(ExportResult {
+        path: destination.to_string_lossy().into_owned(),
+        template_count: manifest.templates.len(),
+        blank_file_count: manifest.blank_files.len(),
+        warnings,
+    })
+}
+
+/// Reads a template from the first of `template_dirs` that has it.
+fn read_template(
+    template_dirs: &[PathBuf],
+    category: Option<&str>,
+    name: &str,
+) -> Result<String, String> {
+    for dir in template_dirs {
+        let path = templates::template_path(dir, category, name)?;
+        if path.is_file() {
+            return fs::read_to_string(&path)
+                .map_err(|e| format!("Failed to read template '{}': {}", name, e));
+        }
+    }
+    Err(format!("Template does not exist: {}", name))
+}
+
+/// Adds the extensions of the files a template creates from scratch. Every
+/// `@if` branch counts, included templates are left to their own entries, and
+/// placeholders take their default value, or `1` so repeaters stay valid.
+fn template_extensions(content: &str, extensions: &mut BTreeSet<String>) -> Result<(), String> {
+    let defaults = variables::template_variables(content)?;
+    let (_, body) = structure::split_frontmatter(content).map_err(|e| e.to_string())?;
+    let mut text = String::with_capacity(body.len());
+    let mut open_block: Option<String> = None;
+    for line in body.split_inclusive('\n') {
+        let entry = line.trim_end_matches(['\n', '\r']);
+        if let Some(delimiter) = &open_block {
+            if entry.trim_start_matches('\t') == delimiter {
+                open_block = None;
+            }
+            text.push_str(line);
+            continue;
+        }
+        if conditions::directive(entry).is_some()
+            || includes::include_reference(entry.trim_start_matches('\t')).is_some()
+        {
+            continue;
+        }
+        open_block = structure::content_delimiter(entry).map(str::to_string);
+        let mut last = 0;
+        for (start, name, end) in variables::placeholders(line) {
+            let value = defaults
+                .iter()
+                .find(|variable| variable.name == name)
+                .and_then(|variable| variable.default.as_deref())
+                .unwrap_or("1");
+            text.push_str(&line[last..start]);
+            text.push_str(value);
+            last = end;
+        }
+        text.push_str(&line[last..]);
+    }
+    let document = structure::parse(&text).map_err(|e| e.to_string())?;
+    collect_extensions(&document.nodes, extensions);
+    Ok(())
+}
+
+/// Lower-cased extensions of the files a structure creates from scratch.
+fn collect_extensions(nodes: &[StructureNode], extensions: &mut BTreeSet<String>) {
+    for node in nodes {
+        if node.kind == NodeKind::File && node.content.is_none() {
+            if let Some(extension) = Path::new(&node.name).extension() {
+                extensions.insert(extension.to_string_lossy().to_lowercase());
+            }
+        }
+        collect_extensions(&node.children, extensions);
+    }
+}
+
+// -----------------
+// Import
+// -----------------
+pub fn read_manifest(pack: &Path) -> Result<PackManifest, String> {
+    let mut archive = open_pack(pack)?;
+    read_manifest_from(&mut archive)
+}
+
+fn open_pack(pack: &Path) -> Result<ZipArchive<fs::File>, String> {
+    let file = fs::File::open(pack).map_err(|e| format!("Failed to open pack: {}", e))?;
+    ZipArchive::new(file).map_err(|e| format!("Not a template pack: {}", e))
+}
+
+fn read_manifest_from(archive: &mut ZipArchive<fs::File>) -> Result<PackManifest, String> {
+    let json = read_entry(archive, MANIFEST_FILE)
+        .map_err(|_| "Not a template pack: manifest.json is missing".to_string())?;
+    let manifest: PackManifest =
+        serde_json::from_slice(&json).map_err(|e| format!("Invalid pack manifest: {}", e))?;
+    if manifest.format != PACK_FORMAT {
+        return Err(format!("Unknown pack format: {}", manifest.format));
+    }
+    if manifest.version > PACK_VERSION {
+        return Err(format!(
+            "This pack needs a newer version of File Architect (pack version {})",
+            manifest.version
+        ));
+    }
+    Ok(manifest)
+}
+
+fn read_entry(archive: &mut ZipArchive<fs::File>, name: &str) -> Result<Vec<u8>, String> {
+    let entry = archive
+        .by_name(name)
+        .map_err(|e| format!("Missing {} in pack: {}", name, e))?;
+    if entry.size() > MAX_ENTRY_SIZE {
+        return Err(format!("{} is too large", name));
+    }
+    let mut bytes = Vec::with_capacity(entry.size() as usize);
+    entry
+        .take(MAX_ENTRY_SIZE)
+        .read_to_end(&mut bytes)
+        .map_err(|e| format!("Failed to read {}: {}", name, e))?;
+    Ok(bytes)
+}
+
+/// Installs the templates of a pack. Names and categories are taken from the
+/// manifest and sanitised; archive paths are only used for lookups.
+pub fn import_templates(
+    templates_dir: &Path,
+    blank_files_dir: Option<&Path>,
+    pack: &Path,
+    policy: ImportConflictPolicy,
+) -> Result<ImportResult, String> {
+    let mut archive = open_pack(pack)?;
+    let manifest = read_manifest_from(&mut archive)?;
+
+    // Read everything first so a broken pack installs nothing.
+    let mut entries = Vec::with_capacity(manifest.templates.len());
+    for template in &manifest.templates {
+        let content = String::from_utf8(read_entry(&mut archive, &template.path)?)
+            .map_err(|_| format!("Template '{}' is not valid text", template.name))?;
+        let category = template.category.as_deref().map(|category| {
+            category
+                .split(['/', '\\'])
+                .filter(|segment| !segment.is_empty())
+                .map(templates::sanitize_name)
+                .collect::<Vec<_>>()
+                .join("/")
+        });
+        let category = category.filter(|category| !category.is_empty());
+        entries.push((templates::sanitize_name(&template.name), category, content));
+    }
+
+    let mut imported = Vec::with_capacity(entries.len());
+    for (name, category, content) in entries {
+        let existing = templates::template_path(templates_dir, category.as_deref(), &name)?;
+        let (name, status) = match (existing.exists(), policy) {
+            (false, _) => (name, ImportStatus::Imported),
+            (true, ImportConflictPolicy::Skip) => {
+                imported.push(ImportedTemplate {
+                    name,
+                    category,
+                    status: ImportStatus::Skipped,
+                });
+                continue;
+            }
+            (true, ImportConflictPolicy::Overwrite) => (name, ImportStatus::Overwritten),
+            (true, ImportConflictPolicy::KeepBoth) => (
+                unused_name(templates_dir, category.as_deref(), &name),
+                ImportStatus::Renamed,
+            ),
+        };
+        templates::save_template(templates_dir, category.as_deref(), &name, &content)?;
+        imported.push(ImportedTemplate {
+            name,
+            category,
+            status,
+        });
+    }
+
+    let mut blank_file_count = 0;
+    if let Some(blank_files_dir) = blank_files_dir {
+        for extension in &manifest.blank_files {
+            let extension = extension.to_lowercase();
+            if extension.is_empty() || !extension.chars().all(|c| c.is_ascii_alphanumeric()) {
+                continue;
+            }
+            let target = blank_files_dir.join(format!("blank.{}", extension));
+            // The user's own blank files win unless overwriting was asked for.
+            if target.exists() && policy != ImportConflictPolicy::Overwrite {
+                continue;
+            }
+            let bytes = read_entry(
+                &mut archive,
+                &format!("{}/blank.{}", BLANK_FILES_DIR, extension),
+            )?;
+            fs::create_dir_all(blank_files_dir).map_err(|e| e.to_string())?;
+            templates::write_atomic(&target, &bytes).map_err(|e| e.to_string())?;
+            blank_file_count += 1;
+        }
+    }
+
+    Ok(ImportResult {
+        templates: imported,
+        blank_file_count,
+    })
+}
+
+/// First of `Name (2)`, `Name (3)`, … that is not taken in `category`.
+fn unused_name(templates_dir: &Path, category: Option<&str>, name: &str) -> String {
+    (2..)
+        .map(|index| format!("{} ({})", name, index))
+        .find(|candidate| {
+            templates::template_path(templates_dir, category, candidate)
+                .is_ok_and(|path| !path.exists())
+        })
+        .unwrap_or_else(|| name.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn exported_packs_import_with_their_includes_and_blank_files() {
+        let dir = tempfile::tempdir().unwrap();
+        let personal = dir.path().join("Personal");
+        let team = dir.path().join("Team");
+        let blank_files = dir.path().join("BlankFiles");
+        let web = "---\nvariables:\n  pages: 2\n---\nsite\n\tpage_$*{{pages}}.docx\n\
+                   \t@if with_sheets\n\tbudget.xlsx\n\t@endif\n\t@include Shared/Base";
+        templates::save_template(&personal, None, "Web", web).unwrap();
+        templates::save_template(&team, Some("Shared"), "Base", "base\n\tslides.pptx").unwrap();
+        fs::create_dir_all(&blank_files).unwrap();
+        for extension in ["docx", "xlsx", "pptx", "pdf"] {
+            fs::write(blank_files.join(format!("blank.{}", extension)), extension).unwrap();
+        }
+
+        let exported = export_templates(
+            &[personal, team],
+            Some(&blank_files),
+            &[TemplateRef {
+                name: "Web".to_string(),
+                category: None,
+            }],
+            &dir.path().join("web"),
+        )
+        .unwrap();
+
+        assert!(exported.path.ends_with("web.fatemplates"));
+        assert_eq!(exported.template_count, 2);
+        assert_eq!(exported.blank_file_count, 3);
+        assert!(exported.warnings.is_empty(), "{:?}", exported.warnings);
+
+        let imported_root = dir.path().join("Imported");
+        let imported_blank_files = dir.path().join("ImportedBlankFiles");
+        let imported = import_templates(
+            &imported_root,
+            Some(&imported_blank_files),
+            Path::new(&exported.path),
+            ImportConflictPolicy::Skip,
+        )
+        .unwrap();
+
+        assert_eq!(imported.templates.len(), 2);
+        assert_eq!(imported.blank_file_count, 3);
+        let read = |category: Option<&str>, name: &str| {
+            fs::read_to_string(templates::template_path(&imported_root, category, name).unwrap())
+                .unwrap()
+        };
+        assert_eq!(read(None, "Web"), web);
+        assert_eq!(read(Some("Shared"), "Base"), "base\n\tslides.pptx");
+        assert!(imported_blank_files.join("blank.xlsx").is_file());
+        assert!(!imported_blank_files.join("blank.pdf").exists());
+    }
+}
@@ -0,0 +1,487 @@
+// Dry-run planning: classifies what each operation would do without touching
+// the disk. Each target directory is listed at most once, so large structures
+// cost one `read_dir` per folder rather than one `stat` per entry; missing
+// folders are resolved through the listings of their ancestors.
+
+use serde::Serialize;
+use std::collections::HashMap;
+use std::ffi::OsString;
+use std::fs;
+use std::io::{self, Read};
+use std::path::{Path, PathBuf};
+
+use crate::executor::{self, ExecuteOptions, Operation, OperationType, StructureCreationSummary};
+
+// -----------------
+// Types
+// -----------------
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub enum PlanStatus {
+    New,
+    IdenticalExists,
+    DiffersExists,
+    /// A file exists where a folder is expected, or the other way round.
+    TypeConflict,
+    MissingSource,
+    PermissionDenied,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PlannedOperation {
+    #[serde(flatten)]
+    pub operation: Operation,
+    pub status: PlanStatus,
+    pub message: Option<String>,
+}
+
+#[derive(Debug, Clone, Default, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PlanTotals {
+    pub new: usize,
+    pub identical_exists: usize,
+    pub differs_exists: usize,
+    pub type_conflict: usize,
+    pub missing_source: usize,
+    pub permission_denied: usize,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct StructurePlan {
+    pub base_dir: String,
+    pub summary: StructureCreationSummary,
+    pub totals: PlanTotals,
+    pub operations: Vec<PlannedOperation>,
+}
+
+#[derive(Debug, Clone, Copy)]
+enum EntryKind {
+    File { len: u64 },
+    Directory,
+}
+
+enum Listing {
+    Entries(HashMap<OsString, EntryKind>),
+    Missing,
+    NotDirectory,
+    Denied,
+}
+
+/// What currently sits at a target path, resolved through cached listings.
+enum TargetState {
+    Missing,
+    Existing(EntryKind),
+    /// An ancestor is a file, so nothing can be created here.
+    UnderFile,
+    Denied,
+}
+
+/// Lazily listed view of the target tree.
+#[derive(Default)]
+struct TargetTree {
+    listings: HashMap<PathBuf, Listing>,
+    writable: HashMap<PathBuf, bool>,
+}
+
+impl TargetTree {
+    fn listing(&mut self, directory: &Path) -> &Listing {
+        self.listings
+            .entry(directory.to_path_buf())
+            .or_insert_with(|| read_listing(directory))
+    }
+
+    fn state(&mut self, path: &Path) -> TargetState {
+        let (Some(parent), Some(name)) = (path.parent(), path.file_name()) else {
+            return match fs::metadata(path) {
+                Ok(metadata) => TargetState::Existing(entry_kind(&metadata)),
+                Err(_) => TargetState::Missing,
+            };
+        };
+        match self.listing(parent) {
+            Listing::Entries(entries) => match entries.get(name) {
+                Some(kind) => TargetState::Existing(*kind),
+                None => TargetState::Missing,
+            },
+            Listing::Missing => TargetState::Missing,
+            Listing::NotDirectory => TargetState::UnderFile,
+            Listing::Denied => TargetState::Denied,
+        }
+    }
+
+    /// Whether new entries can be created at `path`, judged from its nearest
+    /// existing ancestor.
+    fn can_create(&mut self, path: &Path) -> bool {
+        for ancestor in path.ancestors().skip(1) {
+            match self.state(ancestor) {
+                TargetState::Missing => continue,
+                TargetState::Existing(EntryKind::Directory) => {
+                    return *self
+                        .writable
+                        .entry(ancestor.to_path_buf())
+                        .or_insert_with(|| is_writable(ancestor));
+                }
+                _ => return false,
+            }
+        }
+        true
+    }
+}
+
+/// Asks the OS whether the current user may create entries in `directory`,
+/// which takes ownership, groups and ACLs into account.
+#[cfg(unix)]
+fn is_writable(directory: &Path) -> bool {
+    use std::os::unix::ffi::OsStrExt;
+    let Ok(path) = std::ffi::CString::new(directory.as_os_str().as_bytes()) else {
+        return false;
+    };
+    // SAFETY: `path` is a valid NUL-terminated string for the whole call.
+    unsafe { libc::faccessat(libc::AT_FDCWD, path.as_ptr(), libc::W_OK, libc::AT_EACCESS) == 0 }
+}
+
+/// The read-only attribute of a Windows folder does not stop new entries from
+/// being created, so folders are assumed writable and real permission errors
+/// surface when the structure is created.
+#[cfg(not(unix))]
+fn is_writable(_directory: &Path) -> bool {
+    true
+}
+
+fn read_listing(directory: &Path) -> Listing {
+    match fs::read_dir(directory) {
+        Ok(entries) => Listing::Entries(
+            entries
+                .flatten()
+                .filter_map(|entry| {
+                    // Follow symlinks so a linked folder counts as a folder;
+                    // broken links still occupy their name.
+                    let metadata = fs::metadata(entry.path())
+                        .or_else(|_| fs::symlink_metadata(entry.path()))
+                        .ok()?;
+                    Some((entry.file_name(), entry_kind(&metadata)))
+                })
+                .collect(),
+        ),
+        Err(error) if error.kind() == io::ErrorKind::PermissionDenied => Listing::Denied,
+        Err(error) if error.kind() == io::ErrorKind::NotFound => Listing::Missing,
+        Err(_) => Listing::NotDirectory,
+    }
+}
+
+fn entry_kind(metadata: &fs::Metadata) -> EntryKind {
+    if metadata.is_dir() {
+        EntryKind::Directory
+    } else {
+        EntryKind::File {
+            len: metadata.len(),
+        }
+    }
+}
+
+// -----------------
+// Planning
+// -----------------
+pub fn plan(base_dir: &str, operations: &[Operation], options: &ExecuteOptions) -> StructurePlan {
+    let mut tree = TargetTree::default();
+    let mut totals = PlanTotals::default();
+    let mut planned = Vec::with_capacity(operations.len());
+
+    for operation in operations {
+        let (status, message) = classify(operation, options, &mut tree);
+        match status {
+            PlanStatus::New => totals.new += 1,
+            PlanStatus::IdenticalExists => totals.identical_exists += 1,
+            PlanStatus::DiffersExists => totals.differs_exists += 1,
+            PlanStatus::TypeConflict => totals.type_conflict += 1,
+            PlanStatus::MissingSource => totals.missing_source += 1,
+            PlanStatus::PermissionDenied => totals.permission_denied += 1,
+        }
+        planned.push(PlannedOperation {
+            operation: operation.clone(),
+            status,
+            message,
+        });
+    }
+
+    let existing_targets = planned
+        .iter()
+        .filter(|planned| {
+            matches!(
+                planned.status,
+                PlanStatus::IdenticalExists | PlanStatus::DiffersExists | PlanStatus::TypeConflict
+            )
+        })
+        .map(|planned| planned.operation.target_path.clone())
+        .collect();
+
+    StructurePlan {
+        base_dir: base_dir.to_string(),
+        summary: executor::summarize(operations, existing_targets),
+        totals,
+        operations: planned,
+    }
+}
+
+fn classify(
+    operation: &Operation,
+    options: &ExecuteOptions,
+    tree: &mut TargetTree,
+) -> (PlanStatus, Option<String>) {
+    let target = Path::new(&operation.target_path);
+
+    let source = match operation.operation_type {
+        OperationType::Create | OperationType::Symlink => None,
+        OperationType::Copy | OperationType::Move | OperationType::HardLink => {
+            let source = operation.source_path.as_deref().map(PathBuf::from);
+            match source.as_deref().map(fs::metadata) {
+                Some(Ok(metadata))
+                    if metadata.is_dir() && operation.operation_type == OperationType::HardLink =>
+                {
+                    return (
+                        PlanStatus::TypeConflict,
+                        Some("Hard links cannot point to folders".to_string()),
+                    );
+                }
+                Some(Ok(_)) => source,
+                Some(Err(error)) if error.kind() == io::ErrorKind::PermissionDenied => {
+                    return (
+                        PlanStatus::PermissionDenied,
+                        Some(format!("Cannot read source: {}", error)),
+                    );
+                }
+                _ => {
+                    return (
+                        PlanStatus::MissingSource,
+                        operation
+                            .source_path
+                            .as_ref()
+                            .map(|source| format!("Source does not exist: {}", source)),
+                    );
+                }
+            }
+        }
+    };
+
+    match tree.state(target) {
+        TargetState::Denied => (
+            PlanStatus::PermissionDenied,
+            Some("Cannot read the destination folder".to_string()),
+        ),
+        TargetState::UnderFile => (
+            PlanStatus::TypeConflict,
+            Some("A parent of this entry is a file".to_string()),
+        ),
+        TargetState::Missing if tree.can_create(target) => {
+            (PlanStatus::New, dangling_link_message(operation, target))
+        }
+        TargetState::Missing => (
+            PlanStatus::PermissionDenied,
+            Some("The destination folder is read-only".to_string()),
+        ),
+        TargetState::Existing(_) if operation.operation_type == OperationType::Symlink => {
+            existing_link(operation, target)
+        }
+        // Folders are moved with a rename, which cannot merge into an
+        // existing entry.
+        TargetState::Existing(_)
+            if operation.operation_type == OperationType::Move
+                && source.as_deref().is_some_and(Path::is_dir) =>
+        {
+            (
+                PlanStatus::TypeConflict,
+                Some("A folder can only be moved to a name that is not taken".to_string()),
+            )
+        }
+        TargetState::Existing(kind) => {
+            let expects_directory = match source.as_deref() {
+                Some(source) => source.is_dir(),
+                None => operation.is_directory,
+            };
+            match (kind, expects_directory) {
+                (EntryKind::Directory, false) => (
+                    PlanStatus::TypeConflict,
+                    Some("A folder exists where a file is expected".to_string()),
+                ),
+                (EntryKind::File { .. }, true) => (
+                    PlanStatus::TypeConflict,
+                    Some("A file exists where a folder is expected".to_string()),
+                ),
+                (EntryKind::Directory, true) => match source.as_deref() {
+                    Some(source) => compared(directories_identical(source, target)),
+                    None => (PlanStatus::IdenticalExists, None),
+                },
+                (EntryKind::File { len }, false) => match source.as_deref() {
+                    Some(source) => compared(files_identical(source, target)),
+                    None if operation.content.is_some() => {
+                        let content = operation.content.as_deref().unwrap_or_default();
+                        compared(fs::read(target).map(|existing| existing == content.as_bytes()))
+                    }
+                    None => match executor::blank_file_for(target, options) {
+                        Some(blank) => compared(files_identical(&blank, target)),
+                        None if len == 0 => (PlanStatus::IdenticalExists, None),
+                        None => (PlanStatus::DiffersExists, None),
+                    },
+                },
+            }
+        }
+    }
+}
+
+/// Symbolic links may point at something that does not exist yet; that is
+/// allowed but worth pointing out.
+fn dangling_link_message(operation: &Operation, target: &Path) -> Option<String> {
+    if operation.operation_type != OperationType::Symlink {
+        return None;
+    }
+    let source = Path::new(operation.source_path.as_deref()?);
+    (!executor::link_destination(target, source).exists())
+        .then(|| format!("Link target does not exist: {}", source.display()))
+}
+
+fn existing_link(operation: &Operation, target: &Path) -> (PlanStatus, Option<String>) {
+    let source = operation.source_path.as_deref().map(Path::new);
+    match fs::read_link(target) {
+        Ok(existing) if Some(existing.as_path()) == source => (PlanStatus::IdenticalExists, None),
+        Ok(existing) => (
+            PlanStatus::DiffersExists,
+            Some(format!("Existing link points to {}", existing.display())),
+        ),
+        Err(_) if target.is_dir() => (
+            PlanStatus::TypeConflict,
+            Some("A folder exists where a link is expected".to_string()),
+        ),
+        Err(_) => (PlanStatus::DiffersExists, None),
+    }
+}
+
+fn compared(result: io::Result<bool>) -> (PlanStatus, Option<String>) {
+    match result {
+        Ok(true) => (PlanStatus::IdenticalExists, None),
+        Ok(false) => (PlanStatus::DiffersExists, None),
+        Err(error) if error.kind() == io::ErrorKind::PermissionDenied => {
+            (PlanStatus::PermissionDenied, Some(error.to_string()))
+        }
+        Err(error) => (PlanStatus::DiffersExists, Some(error.to_string())),
+    }
+}
+
+fn files_identical(a: &Path, b: &Path) -> io::Result<bool> {
+    if fs::metadata(a)?.len() != fs::metadata(b)?.len() {
+        return Ok(false);
+    }
+    let (mut a, mut b) = (fs::File::open(a)?, fs::File::open(b)?);
+    let (mut buffer_a, mut buffer_b) = ([0u8; 8192], [0u8; 8192]);
+    loop {
+        let read = a.read(&mut buffer_a)?;
+        if read == 0 {
+            return Ok(true);
+        }
+        b.read_exact(&mut buffer_b[..read])?;
+        if buffer_a[..read] != buffer_b[..read] {
+            return Ok(false);
+        }
+    }
+}
+
+/// True when every entry under `source` exists with the same content under
+/// `target`. Extra entries in `target` are ignored since a copy merges.
+fn directories_identical(source: &Path, target: &Path) -> io::Result<bool> {
+    for entry in fs::read_dir(source)? {
+        let entry = entry?;
+        let counterpart = target.join(entry.file_name());
+        let same = if entry.file_type()?.is_dir() {
+            counterpart.is_dir() && directories_identical(&entry.path(), &counterpart)?
+        } else {
+            counterpart.is_file() && files_identical(&entry.path(), &counterpart)?
+        };
+        if !same {
+            return Ok(false);
+        }
+    }
+    Ok(true)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::structure;
+
+    fn statuses(base: &Path, text: &str) -> Vec<PlanStatus> {
+        let document = structure::parse(text).unwrap();
+        let operations = executor::operations_from_document(&document, base);
+        plan(
+            &base.to_string_lossy(),
+            &operations,
+            &ExecuteOptions::default(),
+        )
+        .operations
+        .iter()
+        .map(|planned| planned.status)
+        .collect()
+    }
+
+    #[test]
+    fn existing_entries_are_compared_with_what_would_be_written() {
+        let dir = tempfile::tempdir().unwrap();
+        let base = dir.path();
+        fs::create_dir(base.join("src")).unwrap();
+        fs::write(base.join("src").join("empty.rs"), "").unwrap();
+        fs::write(base.join("src").join("main.rs"), "fn main() {}").unwrap();
+        fs::write(base.join("notes.md"), "notes").unwrap();
+        fs::write(base.join("docs"), "").unwrap();
+
+        let text = "src\n\tempty.rs\n\tmain.rs\n\tnew.rs\n[notes.md] > copy.md\n\
+                    notes.md <<EOF\n\tnotes\nEOF\ndocs/\n\tguide.md\n[missing.md]";
+        assert_eq!(
+            statuses(base, text),
+            [
+                PlanStatus::IdenticalExists,
+                PlanStatus::IdenticalExists,
+                PlanStatus::DiffersExists,
+                PlanStatus::New,
+                PlanStatus::New,
+                PlanStatus::DiffersExists,
+                PlanStatus::TypeConflict,
+                PlanStatus::TypeConflict,
+                PlanStatus::MissingSource,
+            ]
+        );
+    }
+
+    #[test]
+    fn folders_cannot_be_moved_onto_existing_entries() {
+        let dir = tempfile::tempdir().unwrap();
+        let base = dir.path();
+        fs::create_dir_all(base.join("old")).unwrap();
+        fs::create_dir_all(base.join("taken")).unwrap();
+
+        assert_eq!(
+            statuses(base, "(old) > taken\n(old) > free"),
+            [PlanStatus::TypeConflict, PlanStatus::New]
+        );
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn folders_the_user_cannot_write_to_are_reported() {
+        use std::os::unix::fs::PermissionsExt;
+        // Root may write anywhere, so there is nothing to report.
+        if unsafe { libc::geteuid() } == 0 {
+            return;
+        }
+        let dir = tempfile::tempdir().unwrap();
+        let locked = dir.path().join("locked");
+        fs::create_dir(&locked).unwrap();
+        fs::set_permissions(&locked, fs::Permissions::from_mode(0o555)).unwrap();
+
+        let result = statuses(&locked, "project\n\tsrc");
+
+        fs::set_permissions(&locked, fs::Permissions::from_mode(0o755)).unwrap();
+        assert_eq!(
+            result,
+            [PlanStatus::PermissionDenied, PlanStatus::PermissionDenied]
+        );
+    }
+}
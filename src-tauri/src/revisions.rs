@@ -0,0 +1,517 @@
+// Revision history for templates.
+//
+// Before a template is overwritten its previous content is kept in
+// `<templates>/.revisions/<category>/.<name>/<timestamp>.txt`. Only the newest
+// `MAX_REVISIONS` are kept per template.
+//
+// The dot keeps a template's history apart from the category of the same
+// name: template and category names never start with one, so the history of
+// a top-level `Work` template (`.revisions/.Work/`) cannot mix with that of
+// the `Work` category (`.revisions/Work/`).
+
+use serde::Serialize;
+use std::collections::HashMap;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+
+use crate::metadata::millis_since_epoch;
+use crate::templates::{self, TEMPLATE_EXTENSION};
+
+const REVISIONS_DIR: &str = ".revisions";
+const MAX_REVISIONS: usize = 20;
+/// Revision id accepted by `diff_revisions` for the template as it is now.
+const CURRENT_REVISION: &str = "current";
+
+// -----------------
+// Types
+// -----------------
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TemplateRevision {
+    pub id: String,
+    /// Milliseconds since the Unix epoch.
+    pub created_at: u64,
+    pub size: u64,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub enum DiffLineKind {
+    Unchanged,
+    Added,
+    Removed,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DiffLine {
+    pub kind: DiffLineKind,
+    pub text: String,
+    /// 1-based line number in the older text, when the line is in it.
+    pub old_line: Option<usize>,
+    pub new_line: Option<usize>,
+}
+
+// -----------------
+// Recording
+// -----------------
+fn category_history_dir(root: &Path, category: Option<&str>) -> Result<PathBuf, String> {
+    let category_dir = templates::category_dir(root, category)?;
+    let relative = category_dir.strip_prefix(root).unwrap_or(Path::new(""));
+    Ok(root.join(REVISIONS_DIR).join(relative))
+}
+
+fn history_dir(root: &Path, category: Option<&str>, name: &str) -> Result<PathBuf, String> {
+    templates::check_name(name)?;
+    Ok(category_history_dir(root, category)?.join(format!(".{}", name)))
+}
+
+/// Keeps the current content of a template as a revision before it changes.
+/// Does nothing when the template does not exist or `new_content` is the same.
+pub fn record(
+    root: &Path,
+    category: Option<&str>,
+    name: &str,
+    new_content: Option<&str>,
+) -> Result<(), String> {
+    let path = templates::template_path(root, category, name)?;
+    let Ok(current) = fs::read_to_string(&path) else {
+        return Ok(());
+    };
+    if new_content == Some(current.as_str()) {
+        return Ok(());
+    }
+
+    let dir = history_dir(root, category, name)?;
+    fs::create_dir_all(&dir).map_err(|e| e.to_string())?;
+    let mut id = millis_since_epoch(SystemTime::now()).unwrap_or(0);
+    while revision_path(&dir, &id.to_string()).exists() {
+        id += 1;
+    }
+    templates::write_atomic(&revision_path(&dir, &id.to_string()), current.as_bytes())
+        .map_err(|e| e.to_string())?;
+
+    prune(&dir);
+    Ok(())
+}
+
+/// Drops all but the newest `MAX_REVISIONS` revisions in `dir`.
+fn prune(dir: &Path) {
+    for stale in revision_ids(dir).into_iter().skip(MAX_REVISIONS) {
+        let _ = fs::remove_file(revision_path(dir, &stale.to_string()));
+    }
+}
+
+/// Carries the history along when a template is renamed or moved.
+pub fn move_history(
+    root: &Path,
+    (category, name): (Option<&str>, &str),
+    (target_category, target_name): (Option<&str>, &str),
+) -> Result<(), String> {
+    move_dir(
+        &history_dir(root, category, name)?,
+        &history_dir(root, target_category, target_name)?,
+    )
+}
+
+/// Carries the histories of a category's templates along when it is renamed.
+pub fn move_category_history(root: &Path, category: &str, target: &str) -> Result<(), String> {
+    move_dir(
+        &category_history_dir(root, Some(category))?,
+        &category_history_dir(root, Some(target))?,
+    )
+}
+
+/// Drops the history of a deleted template, so a later template of the same
+/// name starts without it.
+pub fn delete_history(root: &Path, category: Option<&str>, name: &str) -> Result<(), String> {
+    let dir = history_dir(root, category, name)?;
+    match fs::remove_dir_all(&dir) {
+        Err(error) if error.kind() != io::ErrorKind::NotFound => Err(error.to_string()),
+        _ => Ok(()),
+    }
+}
+
+/// Moves the history folder `source` to `target`. When `target` already has
+/// history, e.g. left behind by a template deleted outside the app, the two
+/// are merged rather than one replacing the other.
+fn move_dir(source: &Path, target: &Path) -> Result<(), String> {
+    if !source.is_dir() || source == target {
+        return Ok(());
+    }
+    if !target.exists() || templates::is_same_file(source, target) {
+        if let Some(parent) = target.parent() {
+            fs::create_dir_all(parent).map_err(|e| e.to_string())?;
+        }
+        return fs::rename(source, target).map_err(|e| e.to_string());
+    }
+
+    for entry in fs::read_dir(source).map_err(|e| e.to_string())?.flatten() {
+        let path = entry.path();
+        if path.is_dir() {
+            move_dir(&path, &target.join(entry.file_name()))?;
+            continue;
+        }
+        let Some(mut id) = path
+            .file_stem()
+            .and_then(|stem| stem.to_str())
+            .and_then(|stem| stem.parse::<u64>().ok())
+        else {
+            continue;
+        };
+        while revision_path(target, &id.to_string()).exists() {
+            id += 1;
+        }
+        fs::rename(&path, revision_path(target, &id.to_string())).map_err(|e| e.to_string())?;
+    }
+    prune(target);
+    // Only emptied folders go; anything unexpected is left in place.
+    let _ = fs::remove_dir(source);
+    Ok(())
+}
+
+fn revision_path(dir: &Path, id: &str) -> PathBuf {
+    dir.join(format!("{}.{}", id, TEMPLATE_EXTENSION))
+}
+
+/// Revision ids in `dir`, newest first.
+fn revision_ids(dir: &Path) -> Vec<u64> {
+    let mut ids: Vec<u64> = fs::read_dir(dir)
+        .map(|entries| {
+            entries
+                .flatten()
+                .filter_map(|entry| {
+                    let path = entry.path();
+                    if path
+                        .extension()
+                        .map_or(true, |ext| ext != TEMPLATE_EXTENSION)
+                    {
+                        return None;
+                    }
+                    path.file_stem()?.to_str()?.parse().ok()
+                })
+                .collect()
+        })
+        .unwrap_or_default();
+    ids.sort_by_key(|id| std::cmp::Reverse(*id));
+    ids
+}
+
+// -----------------
+// Listing, diffing and restoring
+// -----------------
+pub fn list_revisions(
+    root: &Path,
+    category: Option<&str>,
+    name: &str,
+) -> Result<Vec<TemplateRevision>, String> {
+    let dir = history_dir(root, category, name)?;
+    Ok(revision_ids(&dir)
+        .into_iter()
+        .map(|id| TemplateRevision {
+            id: id.to_string(),
+            created_at: id,
+            size: fs::metadata(revision_path(&dir, &id.to_string()))
+                .map(|metadata| metadata.len())
+                .unwrap_or(0),
+        })
+        .collect())
+}
+
+fn read_revision(
+    root: &Path,
+    category: Option<&str>,
+    name: &str,
+    id: &str,
+) -> Result<String, String> {
+    if id == CURRENT_REVISION {
+        let path = templates::template_path(root, category, name)?;
+        return fs::read_to_string(path).map_err(|e| e.to_string());
+    }
+    if id.is_empty() || !id.chars().all(|c| c.is_ascii_digit()) {
+        return Err(format!("Invalid revision id: {}", id));
+    }
+    let path = revision_path(&history_dir(root, category, name)?, id);
+    fs::read_to_string(path).map_err(|_| format!("Revision {} does not exist", id))
+}
+
+/// Line diff from revision `from` to revision `to`; either may be `current`.
+pub fn diff_revisions(
+    root: &Path,
+    category: Option<&str>,
+    name: &str,
+    from: &str,
+    to: &str,
+) -> Result<Vec<DiffLine>, String> {
+    let old = read_revision(root, category, name, from)?;
+    let new = read_revision(root, category, name, to)?;
+    Ok(diff_lines(&old, &new))
+}
+
+/// Replaces the template with a revision. The content being replaced becomes
+/// a revision itself, so a restore can be undone.
+pub fn restore_revision(
+    root: &Path,
+    category: Option<&str>,
+    name: &str,
+    id: &str,
+) -> Result<(), String> {
+    let content = read_revision(root, category, name, id)?;
+    templates::save_template(root, category, name, &content)
+}
+
+fn intern<'a>(ids: &mut HashMap<&'a str, u32>, lines: &[&'a str]) -> Vec<u32> {
+    lines
+        .iter()
+        .map(|line| {
+            let next = ids.len() as u32;
+            *ids.entry(line).or_insert(next)
+        })
+        .collect()
+}
+
+/// Longest-common-subsequence line diff. Hirschberg's algorithm keeps memory
+/// linear in the number of lines, so even structures of several thousand
+/// lines are diffed without a quadratic table.
+fn diff_lines(old: &str, new: &str) -> Vec<DiffLine> {
+    let old: Vec<&str> = old.lines().collect();
+    let new: Vec<&str> = new.lines().collect();
+
+    // Lines are compared as numbers; equal lines share one.
+    let mut ids: HashMap<&str, u32> = HashMap::new();
+    let old_ids = intern(&mut ids, &old);
+    let new_ids = intern(&mut ids, &new);
+
+    let mut edits = Vec::with_capacity(old.len().max(new.len()));
+    align(&old_ids, &new_ids, &mut edits);
+    removals_first(&mut edits);
+
+    let (mut i, mut j) = (0, 0);
+    edits
+        .into_iter()
+        .map(|kind| match kind {
+            DiffLineKind::Unchanged => {
+                i += 1;
+                j += 1;
+                DiffLine {
+                    kind,
+                    text: old[i - 1].to_string(),
+                    old_line: Some(i),
+                    new_line: Some(j),
+                }
+            }
+            DiffLineKind::Removed => {
+                i += 1;
+                DiffLine {
+                    kind,
+                    text: old[i - 1].to_string(),
+                    old_line: Some(i),
+                    new_line: None,
+                }
+            }
+            DiffLineKind::Added => {
+                j += 1;
+                DiffLine {
+                    kind,
+                    text: new[j - 1].to_string(),
+                    old_line: None,
+                    new_line: Some(j),
+                }
+            }
+        })
+        .collect()
+}
+
+/// Appends the edits turning `old` into `new` to `edits`.
+fn align(old: &[u32], new: &[u32], edits: &mut Vec<DiffLineKind>) {
+    // Common ends need no search.
+    let prefix = old.iter().zip(new).take_while(|(a, b)| a == b).count();
+    let suffix = old[prefix..]
+        .iter()
+        .rev()
+        .zip(new[prefix..].iter().rev())
+        .take_while(|(a, b)| a == b)
+        .count();
+    edits.extend(std::iter::repeat(DiffLineKind::Unchanged).take(prefix));
+    let old_middle = &old[prefix..old.len() - suffix];
+    let new_middle = &new[prefix..new.len() - suffix];
+
+    match (old_middle.len(), new_middle.len()) {
+        (0, added) => edits.extend(std::iter::repeat(DiffLineKind::Added).take(added)),
+        (removed, 0) => edits.extend(std::iter::repeat(DiffLineKind::Removed).take(removed)),
+        (1, _) => match new_middle.iter().position(|line| *line == old_middle[0]) {
+            Some(index) => {
+                edits.extend(std::iter::repeat(DiffLineKind::Added).take(index));
+                edits.push(DiffLineKind::Unchanged);
+                edits.extend(
+                    std::iter::repeat(DiffLineKind::Added).take(new_middle.len() - index - 1),
+                );
+            }
+            None => {
+                edits.push(DiffLineKind::Removed);
+                edits.extend(std::iter::repeat(DiffLineKind::Added).take(new_middle.len()));
+            }
+        },
+        _ => {
+            // Split `old` in half and `new` where the two halves' common
+            // subsequences add up to the longest.
+            let middle = old_middle.len() / 2;
+            let forward = lcs_lengths(old_middle[..middle].iter(), new_middle.iter());
+            let backward = lcs_lengths(old_middle[middle..].iter().rev(), new_middle.iter().rev());
+            let split = (0..=new_middle.len())
+                .max_by_key(|&k| {
+                    (
+                        forward[k] + backward[new_middle.len() - k],
+                        std::cmp::Reverse(k),
+                    )
+                })
+                .unwrap_or(0);
+            align(&old_middle[..middle], &new_middle[..split], edits);
+            align(&old_middle[middle..], &new_middle[split..], edits);
+        }
+    }
+    edits.extend(std::iter::repeat(DiffLineKind::Unchanged).take(suffix));
+}
+
+/// Length of the longest common subsequence of `old` and each prefix of
+/// `new`, computed one row at a time.
+fn lcs_lengths<'a>(
+    old: impl Iterator<Item = &'a u32>,
+    new: impl Iterator<Item = &'a u32> + Clone,
+) -> Vec<usize> {
+    let width = new.clone().count();
+    let mut row = vec![0usize; width + 1];
+    for line in old {
+        let mut diagonal = 0;
+        for (index, other) in new.clone().enumerate() {
+            let above = row[index + 1];
+            row[index + 1] = if line == other {
+                diagonal + 1
+            } else {
+                above.max(row[index])
+            };
+            diagonal = above;
+        }
+    }
+    row
+}
+
+/// Within each run of changes, lists the removed lines before the added ones.
+fn removals_first(edits: &mut [DiffLineKind]) {
+    for run in edits.split_mut(|kind| *kind == DiffLineKind::Unchanged) {
+        run.sort_by_key(|kind| *kind == DiffLineKind::Added);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn kinds(diff: &[DiffLine]) -> String {
+        diff.iter()
+            .map(|line| match line.kind {
+                DiffLineKind::Unchanged => '=',
+                DiffLineKind::Added => '+',
+                DiffLineKind::Removed => '-',
+            })
+            .collect()
+    }
+
+    #[test]
+    fn template_and_category_of_the_same_name_keep_separate_histories() {
+        let root = tempfile::tempdir().unwrap();
+        let root = root.path();
+        templates::save_template(root, Some("Work"), "Client", "v1").unwrap();
+        templates::save_template(root, Some("Work"), "Client", "v2").unwrap();
+        templates::save_template(root, None, "Notes", "n1").unwrap();
+        templates::save_template(root, None, "Notes", "n2").unwrap();
+
+        templates::rename_template(root, None, "Notes", "Work").unwrap();
+        assert_eq!(
+            list_revisions(root, Some("Work"), "Client").unwrap().len(),
+            1
+        );
+        assert_eq!(list_revisions(root, None, "Work").unwrap().len(), 1);
+
+        templates::rename_template(root, None, "Work", "Other").unwrap();
+        assert_eq!(
+            list_revisions(root, Some("Work"), "Client").unwrap().len(),
+            1
+        );
+        assert_eq!(list_revisions(root, None, "Other").unwrap().len(), 1);
+    }
+
+    #[test]
+    fn deleting_a_template_deletes_its_history() {
+        let root = tempfile::tempdir().unwrap();
+        let root = root.path();
+        templates::save_template(root, Some("Work"), "Old", "a").unwrap();
+        templates::save_template(root, Some("Work"), "Old", "b").unwrap();
+        templates::delete_template(root, Some("Work"), "Old").unwrap();
+        templates::save_template(root, Some("Work"), "Old", "c").unwrap();
+
+        assert!(list_revisions(root, Some("Work"), "Old")
+            .unwrap()
+            .is_empty());
+    }
+
+    #[test]
+    fn renaming_onto_leftover_history_merges_it() {
+        let root = tempfile::tempdir().unwrap();
+        let root = root.path();
+        templates::save_template(root, None, "Old", "a").unwrap();
+        templates::save_template(root, None, "Old", "b").unwrap();
+        // Deleted outside the app, so its history is still there.
+        fs::remove_file(templates::template_path(root, None, "Old").unwrap()).unwrap();
+        templates::save_template(root, None, "New", "c").unwrap();
+        templates::save_template(root, None, "New", "d").unwrap();
+
+        templates::rename_template(root, None, "New", "Old").unwrap();
+        assert_eq!(list_revisions(root, None, "Old").unwrap().len(), 2);
+    }
+
+    #[test]
+    fn diff_lists_removals_before_additions() {
+        let diff = diff_lines("a\nb\nc\nd", "a\nx\nc\ny\nz");
+        assert_eq!(kinds(&diff), "=-+=-++");
+        assert_eq!(diff[2].text, "x");
+        assert_eq!((diff[3].old_line, diff[3].new_line), (Some(3), Some(3)));
+        assert_eq!(kinds(&diff_lines("", "a\nb")), "++");
+        assert_eq!(kinds(&diff_lines("a\nb", "")), "--");
+    }
+
+    #[test]
+    fn diff_of_large_structures_is_minimal() {
+        let old: Vec<String> = (0..5000).map(|i| format!("line {}", i)).collect();
+        let new: Vec<String> = old
+            .iter()
+            .enumerate()
+            .filter(|(i, _)| i % 7 != 0)
+            .map(|(i, line)| {
+                if i % 11 == 0 {
+                    format!("{} changed", line)
+                } else {
+                    line.clone()
+                }
+            })
+            .collect();
+        let diff = diff_lines(&old.join("\n"), &new.join("\n"));
+        let unchanged = diff
+            .iter()
+            .filter(|line| line.kind == DiffLineKind::Unchanged)
+            .count();
+        let kept = (0..5000).filter(|i| i % 7 != 0 && i % 11 != 0).count();
+        assert_eq!(unchanged, kept);
+        let removed = diff
+            .iter()
+            .filter(|line| line.kind == DiffLineKind::Removed)
+            .count();
+        let added = diff
+            .iter()
+            .filter(|line| line.kind == DiffLineKind::Added)
+            .count();
+        assert_eq!((removed, added), (5000 - kept, new.len() - kept));
+    }
+}
@@ -0,0 +1,406 @@
+// Turns an existing folder back into structure text, so a "golden" project can
+// be captured as a reusable template.
+//
+// Every line is parsed back before it is written. Entries whose names would be
+// read as structure syntax, such as `(old).txt` or `a -> b.txt`, are skipped
+// and reported, unless the file is written as a `[path]` copy reference anyway.
+// Folders that cannot be read are skipped and reported the same way.
+
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::io;
+use std::path::Path;
+
+use crate::conditions;
+use crate::filter::{EntryFilter, FilterOptions, PatternSet};
+use crate::includes;
+use crate::structure::{self, NodeKind};
+use crate::variables;
+
+const UNREADABLE_NAME: &str = "Its name would be read as structure syntax";
+
+// -----------------
+// Types
+// -----------------
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct ScanOptions {
+    /// How many levels below the root to descend; `None` walks everything.
+    pub max_depth: Option<usize>,
+    #[serde(flatten)]
+    pub filter: FilterOptions,
+    /// Files matching these globs are written as `[path]` copy references so
+    /// their content is copied verbatim instead of created empty.
+    pub copy: Vec<String>,
+    /// Emit the scanned folder itself as the top-level entry.
+    pub include_root: bool,
+}
+
+impl Default for ScanOptions {
+    fn default() -> Self {
+        ScanOptions {
+            max_depth: None,
+            filter: FilterOptions::default(),
+            copy: Vec::new(),
+            include_root: true,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SkippedEntry {
+    /// Path relative to the scanned folder.
+    pub path: String,
+    pub reason: String,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ScannedStructure {
+    pub text: String,
+    pub folder_count: usize,
+    pub file_count: usize,
+    pub link_count: usize,
+    /// Entries left out of `text`, with their contents.
+    pub skipped: Vec<SkippedEntry>,
+}
+
+struct Scanner {
+    filter: EntryFilter,
+    copy: PatternSet,
+    max_depth: Option<usize>,
+    folder_count: usize,
+    file_count: usize,
+    link_count: usize,
+    skipped: Vec<SkippedEntry>,
+}
+
+// -----------------
+// Scanning
+// -----------------
+pub fn scan_to_structure(root: &Path, options: &ScanOptions) -> Result<ScannedStructure, String> {
+    if !root.is_dir() {
+        return Err(format!("Path is not a directory: {}", root.display()));
+    }
+
+    let mut scanner = Scanner {
+        filter: EntryFilter::new(root, &options.filter)?,
+        copy: PatternSet::new(&options.copy)?,
+        max_depth: options.max_depth,
+        folder_count: 0,
+        file_count: 0,
+        link_count: 0,
+        skipped: Vec::new(),
+    };
+
+    let mut lines = Vec::new();
+    if options.include_root {
+        let name = root
+            .file_name()
+            .map(|name| name.to_string_lossy().into_owned())
+            .unwrap_or_else(|| root.to_string_lossy().into_owned());
+        let line =
+            folder_line(&name, 0, true).ok_or_else(|| format!("{}: {}", UNREADABLE_NAME, name))?;
+        lines.push(line);
+    }
+    let level = usize::from(options.include_root);
+    scanner
+        .scan_dir(root, "", 0, level, &mut lines)
+        .map_err(|e| format!("Failed to read {}: {}", root.display(), e))?;
+
+    Ok(ScannedStructure {
+        text: lines.join("\n"),
+        folder_count: scanner.folder_count,
+        file_count: scanner.file_count,
+        link_count: scanner.link_count,
+        skipped: scanner.skipped,
+    })
+}
+
+impl Scanner {
+    /// Appends the lines for the contents of `dir`, which sits `depth` levels
+    /// below the root and is written at indentation `level`. Returns whether
+    /// anything was kept, so folders emptied by `include` can be dropped. Only
+    /// a failure to list `dir` itself is returned; subfolders that cannot be
+    /// listed are skipped.
+    fn scan_dir(
+        &mut self,
+        dir: &Path,
+        relative_dir: &str,
+        depth: usize,
+        level: usize,
+        lines: &mut Vec<String>,
+    ) -> io::Result<bool> {
+        // Links are not followed; they are written back as links.
+        let mut entries: Vec<(String, bool, bool)> = fs::read_dir(dir)?
+            .flatten()
+            .filter_map(|entry| {
+                let file_type = entry.file_type().ok()?;
+                Some((
+                    entry.file_name().to_string_lossy().into_owned(),
+                    file_type.is_dir(),
+                    file_type.is_symlink(),
+                ))
+            })
+            .collect();
+        entries.sort_by(|a, b| match (a.1, b.1) {
+            (true, false) => std::cmp::Ordering::Less,
+            (false, true) => std::cmp::Ordering::Greater,
+            _ => a.0.cmp(&b.0),
+        });
+
+        let mut kept_any = false;
+        for (name, is_directory, is_symlink) in entries {
+            let relative_path = if relative_dir.is_empty() {
+                name.clone()
+            } else {
+                format!("{}/{}", relative_dir, name)
+            };
+            let path = dir.join(&name);
+            if self.filter.is_excluded(&path, &relative_path, is_directory) {
+                continue;
+            }
+
+            if is_symlink {
+                if !self.filter.is_included(&name, &relative_path) {
+                    continue;
+                }
+                let Ok(link_target) = fs::read_link(&path) else {
+                    continue;
+                };
+                let Some(line) = link_line(&name, level, &link_target) else {
+                    self.skip(relative_path, UNREADABLE_NAME);
+                    continue;
+                };
+                lines.push(line);
+                self.link_count += 1;
+                kept_any = true;
+            } else if is_directory {
+                // Whether a trailing `/` is needed is only known once the
+                // children are scanned; it does not change how a name reads.
+                let Some(line) = folder_line(&name, level, true) else {
+                    self.skip(relative_path, UNREADABLE_NAME);
+                    continue;
+                };
+                let mut children = Vec::new();
+                let expand = self.max_depth.map_or(true, |max| depth + 1 < max);
+                let kept_children = if expand {
+                    let loaded = self.filter.enter(&path);
+                    let kept =
+                        self.scan_dir(&path, &relative_path, depth + 1, level + 1, &mut children);
+                    self.filter.leave(loaded);
+                    match kept {
+                        Ok(kept) => kept,
+                        Err(error) => {
+                            self.skip(relative_path, error.to_string());
+                            continue;
+                        }
+                    }
+                } else {
+                    false
+                };
+                if !kept_children && self.filter.has_include_patterns() {
+                    continue;
+                }
+                if kept_children {
+                    lines.push(line);
+                } else {
+                    lines.push(folder_line(&name, level, false).unwrap_or(line));
+                }
+                lines.append(&mut children);
+                self.folder_count += 1;
+                kept_any = true;
+            } else {
+                if !self.filter.is_included(&name, &relative_path) {
+                    continue;
+                }
+                let copy = self.copy.matches(&name, &relative_path);
+                let Some(line) = file_line(&path, &name, level, copy) else {
+                    self.skip(relative_path, UNREADABLE_NAME);
+                    continue;
+                };
+                lines.push(line);
+                self.file_count += 1;
+                kept_any = true;
+            }
+        }
+        Ok(kept_any)
+    }
+
+    fn skip(&mut self, path: String, reason: impl Into<String>) {
+        self.skipped.push(SkippedEntry {
+            path,
+            reason: reason.into(),
+        });
+    }
+}
+
+/// Whether `line`, written without indentation, parses back as a single entry
+/// named `name` of an accepted `kind` with the given `source`.
+fn reads_back(line: &str, name: &str, kind: &[NodeKind], source: Option<&str>) -> bool {
+    if conditions::directive(line).is_some()
+        || includes::include_reference(line).is_some()
+        || !variables::placeholders(line).is_empty()
+    {
+        return false;
+    }
+    match structure::parse(line) {
+        Ok(document) => match document.nodes.as_slice() {
+            [node] => {
+                node.name == name
+                    && kind.contains(&node.kind)
+                    && node.source.as_deref() == source
+                    && node.repeat.is_none()
+                    && node.conflict_policy.is_none()
+                    && node.content.is_none()
+                    && document.frontmatter.is_none()
+            }
+            _ => false,
+        },
+        Err(_) => false,
+    }
+}
+
+/// A childless folder whose name looks like a file (`my.app`) gets a trailing
+/// `/` so it is not parsed back as a file.
+fn folder_line(name: &str, level: usize, has_children: bool) -> Option<String> {
+    let marker = if !has_children && Path::new(name).extension().is_some() {
+        "/"
+    } else {
+        ""
+    };
+    let line = format!("{}{}", name, marker);
+    // On its own a folder with children may parse as a file.
+    let kinds = [NodeKind::Folder, NodeKind::File];
+    reads_back(&line, name, &kinds, None).then(|| format!("{}{}", "\t".repeat(level), line))
+}
+
+fn link_line(name: &str, level: usize, link_target: &Path) -> Option<String> {
+    let target = link_target.to_string_lossy();
+    let line = format!("{} -> {}", name, target);
+    reads_back(&line, name, &[NodeKind::Symlink], Some(&target))
+        .then(|| format!("{}{}", "\t".repeat(level), line))
+}
+
+/// Files without an extension (`LICENSE`, `Makefile`) would be parsed back as
+/// folders, so they are marked with `!file`.
+fn file_line(path: &Path, name: &str, level: usize, copy: bool) -> Option<String> {
+    let line = if copy {
+        let source = path.to_string_lossy();
+        let line = format!("[{}]", source);
+        reads_back(&line, name, &[NodeKind::Copy], Some(&source)).then_some(line)
+    } else if Path::new(name).extension().is_some() {
+        reads_back(name, name, &[NodeKind::File], None).then(|| name.to_string())
+    } else {
+        let line = format!("{} !file", name);
+        reads_back(&line, name, &[NodeKind::File], None).then_some(line)
+    }?;
+    Some(format!("{}{}", "\t".repeat(level), line))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn scan(root: &Path, copy: &[&str]) -> ScannedStructure {
+        let options = ScanOptions {
+            include_root: false,
+            copy: copy.iter().map(|glob| glob.to_string()).collect(),
+            ..Default::default()
+        };
+        scan_to_structure(root, &options).unwrap()
+    }
+
+    fn skipped(scanned: &ScannedStructure) -> Vec<(&str, &str)> {
+        scanned
+            .skipped
+            .iter()
+            .map(|entry| (entry.path.as_str(), entry.reason.as_str()))
+            .collect()
+    }
+
+    fn entries(text: &str) -> Vec<(String, NodeKind)> {
+        structure::parse(text)
+            .unwrap()
+            .nodes
+            .into_iter()
+            .map(|node| (node.name, node.kind))
+            .collect()
+    }
+
+    #[test]
+    fn names_read_as_syntax_are_skipped_unless_copied() {
+        let dir = tempfile::tempdir().unwrap();
+        let root = dir.path().join("golden");
+        for folder in ["(old)", "[assets]", "src"] {
+            fs::create_dir_all(root.join(folder)).unwrap();
+            fs::write(root.join(folder).join("index.js"), "").unwrap();
+        }
+        for file in [
+            "a -> b.txt",
+            "(draft).md",
+            "{{name}}.md",
+            "@include x.txt",
+            "@if x.txt",
+            "notes.md",
+            "LICENSE",
+        ] {
+            fs::write(root.join(file), "").unwrap();
+        }
+
+        let scanned = scan(&root, &[]);
+        assert_eq!(
+            skipped(&scanned),
+            [
+                ("(old)", UNREADABLE_NAME),
+                ("[assets]", UNREADABLE_NAME),
+                ("(draft).md", UNREADABLE_NAME),
+                ("@if x.txt", UNREADABLE_NAME),
+                ("@include x.txt", UNREADABLE_NAME),
+                ("a -> b.txt", UNREADABLE_NAME),
+                ("{{name}}.md", UNREADABLE_NAME),
+            ]
+        );
+        assert_eq!((scanned.folder_count, scanned.file_count), (1, 3));
+        assert!(!scanned.text.contains(&*root.to_string_lossy()));
+        assert_eq!(
+            entries(&scanned.text),
+            [
+                ("src".to_string(), NodeKind::Folder),
+                ("LICENSE".to_string(), NodeKind::File),
+                ("notes.md".to_string(), NodeKind::File),
+            ]
+        );
+
+        let scanned = scan(&root, &["*.md"]);
+        assert_eq!(scanned.skipped.len(), 6);
+        assert_eq!(
+            entries(&scanned.text)[1],
+            ("(draft).md".to_string(), NodeKind::Copy)
+        );
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn unreadable_folders_are_skipped_with_the_reason() {
+        use std::os::unix::fs::PermissionsExt;
+        // Root can list any folder.
+        if unsafe { libc::geteuid() } == 0 {
+            return;
+        }
+        let dir = tempfile::tempdir().unwrap();
+        let root = dir.path().join("golden");
+        fs::create_dir_all(root.join("locked")).unwrap();
+        fs::create_dir_all(root.join("src")).unwrap();
+        fs::set_permissions(root.join("locked"), fs::Permissions::from_mode(0o000)).unwrap();
+
+        let scanned = scan(&root, &[]);
+
+        fs::set_permissions(root.join("locked"), fs::Permissions::from_mode(0o755)).unwrap();
+        assert_eq!(scanned.skipped.len(), 1);
+        assert_eq!(scanned.skipped[0].path, "locked");
+        assert!(scanned.skipped[0].reason.starts_with("Permission denied"));
+        assert_eq!(scanned.text, "src");
+    }
+}
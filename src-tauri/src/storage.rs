@@ -0,0 +1,520 @@
+// Where the app keeps its data: templates, blank files and run history.
+//
+// The data root is, in order of precedence:
+// 1. `FILEARCHITECT_DATA_DIR`, for scripted setups;
+// 2. `dataRoot` in the current workspace's `.filearchitect.json`, relative
+//    to the workspace folder;
+// 3. the root chosen in the app, saved in the user's config folder;
+// 4. `Documents/FileArchitect`.
+
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::RwLock;
+
+use crate::executor::copy_dir_recursive;
+use crate::expand_tilde_path;
+use crate::templates::{write_atomic, TemplateSource, PERSONAL_SOURCE};
+
+pub const DATA_DIR_ENV: &str = "FILEARCHITECT_DATA_DIR";
+pub const WORKSPACE_FILE: &str = ".filearchitect.json";
+const SETTINGS_FILE: &str = "settings.json";
+
+pub const TEMPLATES_FOLDER: &str = "Templates";
+pub const BLANK_FILES_FOLDER: &str = "BlankFiles";
+pub const HISTORY_FOLDER: &str = "History";
+const DATA_FOLDERS: [&str; 3] = [TEMPLATES_FOLDER, BLANK_FILES_FOLDER, HISTORY_FOLDER];
+
+// -----------------
+// Types
+// -----------------
+/// The folder whose `.filearchitect.json` may override the data root. Kept in
+/// the app state, so everything that resolves the data root is handed it.
+#[derive(Debug, Default)]
+pub struct Workspace(RwLock<Option<PathBuf>>);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub enum DataRootSource {
+    Environment,
+    Workspace,
+    Settings,
+    Default,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DataLocation {
+    pub root: String,
+    pub source: DataRootSource,
+    pub workspace: Option<String>,
+    pub templates_dir: String,
+    pub blank_files_dir: String,
+    pub history_dir: String,
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct Settings {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    data_root: Option<String>,
+    /// Template folders read after the personal one, in order of precedence.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    template_sources: Vec<TemplateSource>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct WorkspaceSettings {
+    #[serde(default)]
+    data_root: Option<String>,
+}
+
+// -----------------
+// Settings
+// -----------------
+fn settings_path() -> Option<PathBuf> {
+    dirs::config_dir().map(|dir| dir.join("FileArchitect").join(SETTINGS_FILE))
+}
+
+fn read_settings() -> Settings {
+    settings_path()
+        .and_then(|path| fs::read_to_string(path).ok())
+        .and_then(|json| serde_json::from_str(&json).ok())
+        .unwrap_or_default()
+}
+
+fn write_settings(settings: &Settings) -> Result<(), String> {
+    let path = settings_path().ok_or_else(|| "Could not find config directory".to_string())?;
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent).map_err(|e| e.to_string())?;
+    }
+    let json = serde_json::to_string_pretty(settings).map_err(|e| e.to_string())?;
+    write_atomic(&path, json.as_bytes()).map_err(|e| e.to_string())
+}
+
+fn workspace_root(workspace: &Path) -> Result<Option<PathBuf>, String> {
+    let path = workspace.join(WORKSPACE_FILE);
+    let Ok(json) = fs::read_to_string(&path) else {
+        return Ok(None);
+    };
+    let settings: WorkspaceSettings =
+        serde_json::from_str(&json).map_err(|e| format!("Invalid {}: {}", path.display(), e))?;
+    Ok(settings
+        .data_root
+        .filter(|root| !root.trim().is_empty())
+        .map(|root| workspace.join(expand_tilde_path(root.trim()))))
+}
+
+fn non_empty_env(name: &str) -> Option<String> {
+    std::env::var(name)
+        .ok()
+        .filter(|value| !value.trim().is_empty())
+}
+
+// -----------------
+// Resolution
+// -----------------
+impl Workspace {
+    pub fn path(&self) -> Option<PathBuf> {
+        self.0.read().ok().and_then(|workspace| workspace.clone())
+    }
+
+    /// Sets the folder whose `.filearchitect.json` may override the data
+    /// root, or clears it.
+    pub fn set(&self, path: Option<&str>) -> Result<(), String> {
+        let workspace = match path {
+            Some(path) => {
+                let path = expand_tilde_path(path);
+                if !path.is_dir() {
+                    return Err(format!("Workspace is not a folder: {}", path.display()));
+                }
+                if let Some(root) = workspace_root(&path)? {
+                    check_data_root(&root)?;
+                }
+                Some(path)
+            }
+            None => None,
+        };
+        *self.0.write().map_err(|e| e.to_string())? = workspace;
+        Ok(())
+    }
+}
+
+pub fn resolve(workspace: &Workspace) -> Result<(PathBuf, DataRootSource), String> {
+    if let Some(root) = non_empty_env(DATA_DIR_ENV) {
+        return Ok((expand_tilde_path(root.trim()), DataRootSource::Environment));
+    }
+    if let Some(workspace) = workspace.path() {
+        if let Some(root) = workspace_root(&workspace)? {
+            return Ok((root, DataRootSource::Workspace));
+        }
+    }
+    if let Some(root) = read_settings().data_root {
+        return Ok((expand_tilde_path(&root), DataRootSource::Settings));
+    }
+    let documents =
+        dirs::document_dir().ok_or_else(|| "Could not find documents directory".to_string())?;
+    Ok((documents.join("FileArchitect"), DataRootSource::Default))
+}
+
+pub fn data_root(workspace: &Workspace) -> Result<PathBuf, String> {
+    resolve(workspace).map(|(root, _)| root)
+}
+
+/// The data root is opened to the frontend's file access with everything
+/// below it, so it cannot be the home folder or the root of a drive.
+pub fn check_data_root(root: &Path) -> Result<(), String> {
+    let canonical = |path: PathBuf| fs::canonicalize(&path).unwrap_or(path);
+    let root = canonical(root.to_path_buf());
+    let is_home = dirs::home_dir().is_some_and(|home| canonical(home) == root);
+    if root.parent().is_none() || is_home {
+        return Err(format!(
+            "The data folder needs a folder of its own, not {}",
+            root.display()
+        ));
+    }
+    Ok(())
+}
+
+pub fn location(workspace: &Workspace) -> Result<DataLocation, String> {
+    let (root, source) = resolve(workspace)?;
+    let folder = |name: &str| root.join(name).to_string_lossy().into_owned();
+    Ok(DataLocation {
+        templates_dir: folder(TEMPLATES_FOLDER),
+        blank_files_dir: folder(BLANK_FILES_FOLDER),
+        history_dir: folder(HISTORY_FOLDER),
+        root: root.to_string_lossy().into_owned(),
+        source,
+        workspace: workspace
+            .path()
+            .map(|path| path.to_string_lossy().into_owned()),
+    })
+}
+
+/// Saves the data root chosen in the app, or goes back to the default. Data is
+/// not moved; see `migrate`.
+pub fn set_configured_root(path: Option<&str>) -> Result<(), String> {
+    let data_root = match path.map(str::trim).filter(|path| !path.is_empty()) {
+        Some(path) => {
+            let root = expand_tilde_path(path);
+            if !root.is_absolute() {
+                return Err(format!("Data folder must be an absolute path: {}", path));
+            }
+            check_data_root(&root)?;
+            Some(root.to_string_lossy().into_owned())
+        }
+        None => None,
+    };
+    let mut settings = read_settings();
+    settings.data_root = data_root;
+    write_settings(&settings)
+}
+
+/// The personal templates folder followed by the configured sources.
+pub fn template_sources(workspace: &Workspace) -> Result<Vec<TemplateSource>, String> {
+    let personal = TemplateSource {
+        name: PERSONAL_SOURCE.to_string(),
+        path: data_root(workspace)?
+            .join(TEMPLATES_FOLDER)
+            .to_string_lossy()
+            .into_owned(),
+        read_only: false,
+    };
+    let mut sources = vec![personal];
+    sources.extend(read_settings().template_sources);
+    Ok(sources)
+}
+
+/// The folders of `template_sources`, in the order their templates take
+/// precedence.
+pub fn template_dirs(workspace: &Workspace) -> Result<Vec<PathBuf>, String> {
+    Ok(template_sources(workspace)?
+        .iter()
+        .map(|source| expand_tilde_path(&source.path))
+        .collect())
+}
+
+/// Saves the sources read after the personal templates.
+pub fn set_template_sources(sources: Vec<TemplateSource>) -> Result<(), String> {
+    for (index, source) in sources.iter().enumerate() {
+        let name = source.name.trim();
+        if name.is_empty() {
+            return Err("Template sources need a name".to_string());
+        }
+        if name.eq_ignore_ascii_case(PERSONAL_SOURCE)
+            || sources[..index]
+                .iter()
+                .any(|other| other.name.trim().eq_ignore_ascii_case(name))
+        {
+            return Err(format!(
+                "There is already a template source named '{}'",
+                name
+            ));
+        }
+        if !expand_tilde_path(source.path.trim()).is_absolute() {
+            return Err(format!("Template source '{}' needs an absolute path", name));
+        }
+    }
+    let mut settings = read_settings();
+    settings.template_sources = sources
+        .into_iter()
+        .map(|source| TemplateSource {
+            name: source.name.trim().to_string(),
+            path: source.path.trim().to_string(),
+            read_only: source.read_only,
+        })
+        .collect();
+    write_settings(&settings)
+}
+
+fn find_template_source(workspace: &Workspace, name: &str) -> Result<TemplateSource, String> {
+    template_sources(workspace)?
+        .into_iter()
+        .find(|source| source.name == name)
+        .ok_or_else(|| format!("No template source named '{}'", name))
+}
+
+/// Folder of a template source, whether or not it may be written to.
+pub fn template_source_dir(workspace: &Workspace, name: &str) -> Result<PathBuf, String> {
+    Ok(expand_tilde_path(
+        &find_template_source(workspace, name)?.path,
+    ))
+}
+
+/// Folder of a template source that may be written to.
+pub fn writable_template_source(workspace: &Workspace, name: &str) -> Result<PathBuf, String> {
+    let source = find_template_source(workspace, name)?;
+    if source.read_only {
+        return Err(format!("Template source '{}' is read-only", name));
+    }
+    Ok(expand_tilde_path(&source.path))
+}
+
+/// Moves the data folders from the current root into `destination` and makes
+/// it the configured root. Nothing is moved if `destination` already holds
+/// data, or if the root comes from the environment or a workspace, which
+/// would keep pointing at the old location. If any step fails, the folders
+/// already moved are put back.
+pub fn migrate(workspace: &Workspace, destination: &str) -> Result<Vec<String>, String> {
+    let (root, source) = resolve(workspace)?;
+    match source {
+        DataRootSource::Environment => {
+            return Err(format!(
+                "The data folder is set by {}; change the variable instead",
+                DATA_DIR_ENV
+            ))
+        }
+        DataRootSource::Workspace => {
+            return Err(format!(
+                "The data folder is set by this workspace's {}; change it there instead",
+                WORKSPACE_FILE
+            ))
+        }
+        DataRootSource::Settings | DataRootSource::Default => {}
+    }
+
+    let target = expand_tilde_path(destination.trim());
+    if !target.is_absolute() {
+        return Err(format!(
+            "Data folder must be an absolute path: {}",
+            destination
+        ));
+    }
+    if target == root {
+        return Err("The data is already in this folder".to_string());
+    }
+    if target.starts_with(&root) {
+        return Err("The new data folder cannot be inside the current one".to_string());
+    }
+    check_data_root(&target)?;
+    for folder in DATA_FOLDERS {
+        let existing = target.join(folder);
+        let occupied = fs::read_dir(&existing).is_ok_and(|mut entries| entries.next().is_some());
+        if occupied || existing.is_file() {
+            return Err(format!(
+                "{} already exists and is not empty",
+                existing.display()
+            ));
+        }
+    }
+
+    fs::create_dir_all(&target).map_err(|e| e.to_string())?;
+    let staged = stage_folders(&root, &target)?;
+    if let Err(error) = set_configured_root(Some(&target.to_string_lossy())) {
+        return Err(unstage(&staged, error));
+    }
+    // The new root is in use; what is left behind of a copied folder is only
+    // a duplicate, so failing to delete it does not fail the migration.
+    for folder in &staged {
+        if folder.copied {
+            let _ = fs::remove_dir_all(&folder.from);
+        }
+    }
+    Ok(staged
+        .iter()
+        .map(|folder| folder.name.to_string())
+        .collect())
+}
+
+/// A data folder that has been brought to the new root.
+struct StagedFolder {
+    name: &'static str,
+    from: PathBuf,
+    to: PathBuf,
+    /// Copied across volumes; the original is kept until the move succeeds.
+    copied: bool,
+}
+
+/// Renames each data folder into `target`, or copies it when the two are on
+/// different volumes. Nothing is deleted, so a failure can be undone.
+fn stage_folders(root: &Path, target: &Path) -> Result<Vec<StagedFolder>, String> {
+    let mut staged = Vec::new();
+    for name in DATA_FOLDERS {
+        let from = root.join(name);
+        if !from.exists() {
+            continue;
+        }
+        let to = target.join(name);
+        match stage_folder(&from, &to) {
+            Ok(copied) => staged.push(StagedFolder {
+                name,
+                from,
+                to,
+                copied,
+            }),
+            Err(error) => {
+                let error = format!("Could not move {}: {}", from.display(), error);
+                return Err(unstage(&staged, error));
+            }
+        }
+    }
+    Ok(staged)
+}
+
+/// Returns whether the folder had to be copied.
+fn stage_folder(from: &Path, to: &Path) -> io::Result<bool> {
+    // An empty folder may be in the way; `migrate` refuses non-empty ones.
+    if to.is_dir() {
+        fs::remove_dir(to)?;
+    }
+    if fs::rename(from, to).is_ok() {
+        return Ok(false);
+    }
+    let copied = copy_dir_recursive(from, to);
+    if copied.is_err() {
+        let _ = fs::remove_dir_all(to);
+    }
+    copied.map(|()| true)
+}
+
+/// Puts staged folders back and returns `error`, extended with any folder
+/// that could not be put back.
+fn unstage(staged: &[StagedFolder], error: String) -> String {
+    let mut stranded = Vec::new();
+    for folder in staged.iter().rev() {
+        let restored = if folder.copied {
+            fs::remove_dir_all(&folder.to)
+        } else {
+            fs::rename(&folder.to, &folder.from)
+        };
+        if restored.is_err() {
+            stranded.push(folder.to.display().to_string());
+        }
+    }
+    if stranded.is_empty() {
+        error
+    } else {
+        format!("{}; left in the new folder: {}", error, stranded.join(", "))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::executor::{self, ExecuteOptions};
+    use crate::journal;
+    use crate::structure;
+
+    #[test]
+    fn failed_migration_puts_moved_folders_back() {
+        let dir = tempfile::tempdir().unwrap();
+        let root = dir.path().join("old");
+        let target = dir.path().join("new");
+        fs::create_dir_all(root.join(TEMPLATES_FOLDER)).unwrap();
+        fs::write(root.join(TEMPLATES_FOLDER).join("Web.txt"), "web").unwrap();
+        fs::create_dir_all(root.join(HISTORY_FOLDER)).unwrap();
+        // A non-empty folder in the way makes the last move fail.
+        fs::create_dir_all(target.join(HISTORY_FOLDER)).unwrap();
+        fs::write(target.join(HISTORY_FOLDER).join("run.json"), "{}").unwrap();
+
+        let error = stage_folders(&root, &target).err().unwrap();
+        assert!(error.starts_with("Could not move"), "{}", error);
+        assert_eq!(
+            fs::read_to_string(root.join(TEMPLATES_FOLDER).join("Web.txt")).unwrap(),
+            "web"
+        );
+        assert!(root.join(HISTORY_FOLDER).is_dir());
+        assert!(!target.join(TEMPLATES_FOLDER).exists());
+    }
+
+    #[test]
+    fn staged_folders_can_be_put_back() {
+        let dir = tempfile::tempdir().unwrap();
+        let root = dir.path().join("old");
+        let target = dir.path().join("new");
+        fs::create_dir_all(root.join(BLANK_FILES_FOLDER)).unwrap();
+        fs::create_dir_all(&target).unwrap();
+
+        let staged = stage_folders(&root, &target).unwrap();
+        assert_eq!(staged.len(), 1);
+        assert!(target.join(BLANK_FILES_FOLDER).is_dir());
+        assert_eq!(unstage(&staged, "failed".to_string()), "failed");
+        assert!(root.join(BLANK_FILES_FOLDER).is_dir());
+        assert!(!target.join(BLANK_FILES_FOLDER).exists());
+    }
+
+    #[test]
+    fn the_home_folder_and_filesystem_roots_are_not_data_roots() {
+        let dir = tempfile::tempdir().unwrap();
+        assert!(check_data_root(dir.path()).is_ok());
+        assert!(check_data_root(&dir.path().join("FileArchitect")).is_ok());
+        assert!(check_data_root(Path::new("/")).is_err());
+        if let Some(home) = dirs::home_dir() {
+            assert!(check_data_root(&home).is_err());
+        }
+    }
+
+    #[test]
+    fn runs_can_be_reverted_after_the_data_root_moved() {
+        let dir = tempfile::tempdir().unwrap();
+        let (root, target) = (dir.path().join("old"), dir.path().join("new"));
+        let base = dir.path().join("base");
+        fs::create_dir_all(&base).unwrap();
+        fs::write(base.join("README.md"), "original").unwrap();
+
+        let document = structure::parse("README.md <<EOF\n\tnew\nEOF").unwrap();
+        let options = ExecuteOptions {
+            history_dir: Some(root.join(HISTORY_FOLDER)),
+            ..ExecuteOptions::default()
+        };
+        let result = executor::execute(
+            &base.to_string_lossy(),
+            &executor::operations_from_document(&document, &base),
+            &options,
+        );
+        let run_id = result.run_id.unwrap();
+
+        fs::create_dir_all(&target).unwrap();
+        stage_folders(&root, &target).unwrap();
+        assert!(!root.join(HISTORY_FOLDER).exists());
+
+        let reverted = journal::revert_run(&target.join(HISTORY_FOLDER), &run_id).unwrap();
+        assert!(reverted.skipped.is_empty(), "{:?}", reverted.skipped);
+        assert_eq!(
+            fs::read_to_string(base.join("README.md")).unwrap(),
+            "original"
+        );
+    }
+}
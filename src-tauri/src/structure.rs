@@ -0,0 +1,787 @@
+// Parser for the tab-indented structure language.
+//
+// A structure is an optional `---` frontmatter block followed by one entry per
+// line, nested with tabs:
+//
+//   project
+//   	src
+//   		index.js
+//   	[~/Desktop/logo.png] > logo.png    copy (optionally renamed)
+//   	(~/Downloads/notes.md)             move (optionally renamed)
+//   	current -> ../releases/v2          symbolic link
+//   	data.bin => ~/shared/data.bin      hard link
+//   	chapter_$*3                        repeater: chapter_1 … chapter_3
+//   	README.md !skip                    per-line conflict policy
+//   	LICENSE !file                      file without an extension
+//   	.gitignore <<EOF                   file content, up to a line holding
+//   	node_modules                       only `EOF`; one extra tab of
+//   	EOF                                indentation is stripped per line
+//
+// A plain entry is a folder when it has children, ends with `/` or has no
+// extension, so `LICENSE` or `Makefile` need `!file` (or a content block) to
+// become files. Every content line, the last one included, ends with a line
+// break; a block with one line `node_modules` writes `node_modules\n`.
+
+use serde::{Deserialize, Serialize};
+use std::fmt;
+
+// -----------------
+// Types
+// -----------------
+
+/// A location in the source text. Lines and columns are 1-based and counted in
+/// characters; `end_column` is exclusive.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Span {
+    pub line: usize,
+    pub column: usize,
+    pub end_line: usize,
+    pub end_column: usize,
+}
+
+impl Span {
+    fn on_line(line: usize, column: usize, end_column: usize) -> Self {
+        Span {
+            line,
+            column,
+            end_line: line,
+            end_column,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Frontmatter {
+    pub raw: String,
+    pub span: Span,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub enum NodeKind {
+    Folder,
+    File,
+    Copy,
+    Move,
+    Symlink,
+    HardLink,
+}
+
+/// What to do when a target already exists. Chosen per run and overridable per
+/// line with a trailing `!skip`, `!overwrite`, `!rename` or `!abort`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum ConflictPolicy {
+    Skip,
+    #[default]
+    Overwrite,
+    /// Create alongside with a numeric suffix, e.g. `file (2).txt`.
+    Rename,
+    Abort,
+}
+
+impl ConflictPolicy {
+    fn from_annotation(annotation: &str) -> Option<Self> {
+        match annotation {
+            "!skip" => Some(ConflictPolicy::Skip),
+            "!overwrite" => Some(ConflictPolicy::Overwrite),
+            "!rename" => Some(ConflictPolicy::Rename),
+            "!abort" => Some(ConflictPolicy::Abort),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct StructureNode {
+    pub kind: NodeKind,
+    /// Target name, with any `$*N` repeater still in place.
+    pub name: String,
+    /// Source path of a `[copy]` or `(move)` line, or the target of a link,
+    /// exactly as written.
+    pub source: Option<String>,
+    /// Number of copies requested by a `$*N` repeater in the name.
+    pub repeat: Option<usize>,
+    pub level: usize,
+    pub span: Span,
+    pub name_span: Span,
+    pub source_span: Option<Span>,
+    pub conflict_policy: Option<ConflictPolicy>,
+    /// Set by a trailing `!file`: the entry is a file even without an extension.
+    pub explicit_file: bool,
+    /// Body of a `<<EOF` content block, with its line endings as written.
+    pub content: Option<String>,
+    pub children: Vec<StructureNode>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct StructureDocument {
+    pub frontmatter: Option<Frontmatter>,
+    pub nodes: Vec<StructureNode>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ParseError {
+    pub message: String,
+    pub span: Span,
+}
+
+impl ParseError {
+    fn new(message: impl Into<String>, span: Span) -> Self {
+        ParseError {
+            message: message.into(),
+            span,
+        }
+    }
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "Line {}, column {}: {}",
+            self.span.line, self.span.column, self.message
+        )
+    }
+}
+
+// -----------------
+// Parsing
+// -----------------
+const FRONTMATTER_DELIMITER: &str = "---";
+const REPEATER_MARKER: &str = "$*";
+const SYMLINK_ARROW: &str = "->";
+const HARD_LINK_ARROW: &str = "=>";
+const CONTENT_MARKER: &str = "<<";
+const FILE_ANNOTATION: &str = "!file";
+
+pub fn parse(text: &str) -> Result<StructureDocument, ParseError> {
+    // Content blocks keep their `\r`; everything else ignores it.
+    let raw_lines: Vec<&str> = text.split('\n').collect();
+    let lines: Vec<&str> = raw_lines
+        .iter()
+        .map(|line| line.strip_suffix('\r').unwrap_or(line))
+        .collect();
+
+    let (frontmatter, body_start) = parse_frontmatter(&lines)?;
+
+    // Entries in document order, folded into a tree below.
+    let mut flat: Vec<StructureNode> = Vec::new();
+    let mut previous_level: Option<usize> = None;
+
+    let mut rows = lines.iter().enumerate().skip(body_start);
+    while let Some((index, raw_line)) = rows.next() {
+        let line_number = index + 1;
+        let line = raw_line.trim_end();
+        if line.trim().is_empty() {
+            continue;
+        }
+
+        let level = line.chars().take_while(|c| *c == '\t').count();
+        let rest = &line[level..];
+        if rest.starts_with(' ') {
+            return Err(ParseError::new(
+                "Indentation must use tabs",
+                Span::on_line(line_number, 1, level + leading_spaces(rest) + 1),
+            ));
+        }
+
+        let allowed = previous_level.map_or(0, |previous| previous + 1);
+        if level > allowed {
+            let message = match previous_level {
+                None => "The first entry cannot be indented".to_string(),
+                Some(previous) => format!(
+                    "Indentation jumps from level {} to level {}",
+                    previous, level
+                ),
+            };
+            return Err(ParseError::new(
+                message,
+                Span::on_line(line_number, 1, level + 1),
+            ));
+        }
+        previous_level = Some(level);
+
+        let (mut node, delimiter) = parse_entry(rest, line_number, level)?;
+        if let Some(delimiter) = delimiter {
+            let indent = "\t".repeat(level + 1);
+            let mut content = String::new();
+            let mut closed = false;
+            for (content_index, content_line) in rows.by_ref() {
+                if content_line.trim_start_matches('\t') == delimiter {
+                    closed = true;
+                    break;
+                }
+                let raw = raw_lines[content_index];
+                content.push_str(raw.strip_prefix(indent.as_str()).unwrap_or(raw));
+                content.push('\n');
+            }
+            if !closed {
+                return Err(ParseError::new(
+                    format!("Content block is not closed with '{}'", delimiter),
+                    node.span,
+                ));
+            }
+            node.content = Some(content);
+        }
+        flat.push(node);
+    }
+
+    let mut nodes = build_tree(flat);
+    finalize_kinds(&mut nodes)?;
+
+    Ok(StructureDocument { frontmatter, nodes })
+}
+
+/// Splits `text` into its frontmatter and the structure body that follows it.
+pub fn split_frontmatter(text: &str) -> Result<(Option<Frontmatter>, &str), ParseError> {
+    let lines: Vec<&str> = text
+        .split('\n')
+        .map(|line| line.strip_suffix('\r').unwrap_or(line))
+        .collect();
+    let (frontmatter, body_start) = parse_frontmatter(&lines)?;
+    let offset = text
+        .split_inclusive('\n')
+        .take(body_start)
+        .map(str::len)
+        .sum();
+    Ok((frontmatter, &text[offset..]))
+}
+
+fn leading_spaces(text: &str) -> usize {
+    text.chars().take_while(|c| *c == ' ').count()
+}
+
+fn parse_frontmatter(lines: &[&str]) -> Result<(Option<Frontmatter>, usize), ParseError> {
+    if lines.first().map(|line| line.trim_end()) != Some(FRONTMATTER_DELIMITER) {
+        return Ok((None, 0));
+    }
+
+    let closing = lines
+        .iter()
+        .enumerate()
+        .skip(1)
+        .find(|(_, line)| line.trim_end() == FRONTMATTER_DELIMITER)
+        .map(|(index, _)| index)
+        .ok_or_else(|| {
+            ParseError::new(
+                "Frontmatter block is not closed with '---'",
+                Span::on_line(1, 1, FRONTMATTER_DELIMITER.len() + 1),
+            )
+        })?;
+
+    let frontmatter = Frontmatter {
+        raw: lines[1..closing].join("\n"),
+        span: Span {
+            line: 1,
+            column: 1,
+            end_line: closing + 1,
+            end_column: FRONTMATTER_DELIMITER.len() + 1,
+        },
+    };
+    Ok((Some(frontmatter), closing + 1))
+}
+
+/// Parses the text of a single line after its indentation. Also returns the
+/// delimiter of a content block opened on the line, if any.
+fn parse_entry(
+    entry: &str,
+    line: usize,
+    level: usize,
+) -> Result<(StructureNode, Option<String>), ParseError> {
+    let (text, annotations, delimiter) = split_suffixes(entry);
+    // Columns are 1-based and the entry starts right after `level` tabs.
+    let start_column = level + 1;
+    let column_of = |byte_offset: usize| start_column + text[..byte_offset].chars().count();
+
+    let (kind, source, source_span, name, name_start) = match text.chars().next() {
+        Some(open @ ('[' | '(')) => {
+            let (close, kind) = if open == '[' {
+                (']', NodeKind::Copy)
+            } else {
+                (')', NodeKind::Move)
+            };
+            let close_index = text.find(close).ok_or_else(|| {
+                ParseError::new(
+                    format!("Missing closing '{}'", close),
+                    Span::on_line(line, start_column, column_of(text.len())),
+                )
+            })?;
+            let source = text[1..close_index].trim();
+            if source.is_empty() {
+                return Err(ParseError::new(
+                    "Source path cannot be empty",
+                    Span::on_line(line, start_column, column_of(close_index + 1)),
+                ));
+            }
+            let source_offset = 1 + text[1..].find(source).unwrap_or(0);
+            let source_span = Span::on_line(
+                line,
+                column_of(source_offset),
+                column_of(source_offset + source.len()),
+            );
+
+            let after = &text[close_index + 1..];
+            let after_trimmed = after.trim_start();
+            let after_offset = close_index + 1 + (after.len() - after_trimmed.len());
+            let (name, name_start) = if after_trimmed.is_empty() {
+                let trimmed_source = source.trim_end_matches(['/', '\\']);
+                let name = source_file_name(source);
+                (
+                    name.to_string(),
+                    source_offset + trimmed_source.len() - name.len(),
+                )
+            } else if let Some(rename) = after_trimmed.strip_prefix('>') {
+                let rename_trimmed = rename.trim_start();
+                let rename_offset = after_offset + 1 + (rename.len() - rename_trimmed.len());
+                if rename_trimmed.is_empty() {
+                    return Err(ParseError::new(
+                        "Missing name after '>'",
+                        Span::on_line(line, column_of(after_offset), column_of(text.len())),
+                    ));
+                }
+                (rename_trimmed.to_string(), rename_offset)
+            } else {
+                return Err(ParseError::new(
+                    format!("Unexpected text after '{}'", close),
+                    Span::on_line(line, column_of(after_offset), column_of(text.len())),
+                ));
+            };
+            (
+                kind,
+                Some(source.to_string()),
+                Some(source_span),
+                name,
+                name_start,
+            )
+        }
+        _ => match find_link_arrow(text) {
+            Some((arrow_index, arrow, kind)) => {
+                let name = text[..arrow_index].trim_end();
+                if name.is_empty() {
+                    return Err(ParseError::new(
+                        format!("Missing link name before '{}'", arrow),
+                        Span::on_line(line, start_column, column_of(arrow_index + arrow.len())),
+                    ));
+                }
+                let after = &text[arrow_index + arrow.len()..];
+                let target = after.trim();
+                if target.is_empty() {
+                    return Err(ParseError::new(
+                        "Link target cannot be empty",
+                        Span::on_line(line, column_of(arrow_index), column_of(text.len())),
+                    ));
+                }
+                let target_offset =
+                    arrow_index + arrow.len() + (after.len() - after.trim_start().len());
+                let source_span = Span::on_line(
+                    line,
+                    column_of(target_offset),
+                    column_of(target_offset + target.len()),
+                );
+                (
+                    kind,
+                    Some(target.to_string()),
+                    Some(source_span),
+                    name.trim_end_matches('/').to_string(),
+                    0,
+                )
+            }
+            // Folder or file; the final kind is settled once children are known.
+            None => (NodeKind::File, None, None, text.to_string(), 0),
+        },
+    };
+
+    let name_span = Span::on_line(
+        line,
+        column_of(name_start),
+        column_of(name_start + name.len()),
+    );
+
+    let repeat = parse_repeater(&name, name_span)?;
+
+    if delimiter.is_some() && kind != NodeKind::File {
+        return Err(ParseError::new(
+            "Only files can have a content block",
+            Span::on_line(line, start_column, column_of(text.len())),
+        ));
+    }
+    if annotations.file && (kind != NodeKind::File || name.ends_with('/')) {
+        return Err(ParseError::new(
+            "Only plain names can be marked with !file",
+            Span::on_line(line, start_column, column_of(text.len())),
+        ));
+    }
+
+    let node = StructureNode {
+        kind,
+        name,
+        source,
+        repeat,
+        level,
+        span: Span::on_line(line, 1, start_column + entry.chars().count()),
+        name_span,
+        source_span,
+        conflict_policy: annotations.conflict_policy,
+        explicit_file: annotations.file,
+        content: None,
+        children: Vec::new(),
+    };
+    Ok((node, delimiter.map(str::to_string)))
+}
+
+/// Finds a ` -> ` (symbolic) or ` => ` (hard) link arrow. The arrow must be
+/// surrounded by whitespace so names like `a->b.txt` stay plain files.
+fn find_link_arrow(text: &str) -> Option<(usize, &'static str, NodeKind)> {
+    [
+        (SYMLINK_ARROW, NodeKind::Symlink),
+        (HARD_LINK_ARROW, NodeKind::HardLink),
+    ]
+    .into_iter()
+    .filter_map(|(arrow, kind)| {
+        text.match_indices(arrow)
+            .find(|(index, _)| {
+                let before = text[..*index].chars().next_back();
+                let after = text[index + arrow.len()..].chars().next();
+                before.is_some_and(char::is_whitespace) && after.map_or(true, char::is_whitespace)
+            })
+            .map(|(index, _)| (index, arrow, kind))
+    })
+    .min_by_key(|(index, _, _)| *index)
+}
+
+/// The delimiter of a content block opened by `line`, if it opens one.
+pub fn content_delimiter(line: &str) -> Option<&str> {
+    let entry = line.trim_start_matches('\t').trim_end();
+    split_suffixes(entry).2
+}
+
+/// Splits a trailing `<<DELIMITER` content marker off an entry.
+fn split_content_marker(text: &str) -> (&str, Option<&str>) {
+    let Some((rest, marker)) = text.rsplit_once(char::is_whitespace) else {
+        return (text, None);
+    };
+    match marker.strip_prefix(CONTENT_MARKER) {
+        Some(delimiter)
+            if !delimiter.is_empty()
+                && !rest.trim().is_empty()
+                && delimiter
+                    .chars()
+                    .all(|c| c.is_ascii_alphanumeric() || c == '_') =>
+        {
+            (rest.trim_end(), Some(delimiter))
+        }
+        _ => (text, None),
+    }
+}
+
+/// Splits the trailing annotations and content marker off an entry. The
+/// annotations may come before or after the marker.
+fn split_suffixes(entry: &str) -> (&str, Annotations, Option<&str>) {
+    let (text, annotations) = split_annotations(entry, Annotations::default());
+    match split_content_marker(text) {
+        (rest, Some(delimiter)) => {
+            let (rest, annotations) = split_annotations(rest, annotations);
+            (rest, annotations, Some(delimiter))
+        }
+        (text, None) => (text, annotations, None),
+    }
+}
+
+#[derive(Debug, Default, Clone, Copy)]
+struct Annotations {
+    conflict_policy: Option<ConflictPolicy>,
+    file: bool,
+}
+
+/// Splits trailing annotations (`name !file !skip`) off an entry, in any order.
+/// Each kind is taken once; a repeated one is left as part of the name.
+fn split_annotations(entry: &str, mut annotations: Annotations) -> (&str, Annotations) {
+    let mut text = entry;
+    while let Some((rest, annotation)) = text.rsplit_once(char::is_whitespace) {
+        if rest.trim().is_empty() {
+            break;
+        }
+        if annotation == FILE_ANNOTATION && !annotations.file {
+            annotations.file = true;
+        } else if let Some(policy) = ConflictPolicy::from_annotation(annotation)
+            .filter(|_| annotations.conflict_policy.is_none())
+        {
+            annotations.conflict_policy = Some(policy);
+        } else {
+            break;
+        }
+        text = rest.trim_end();
+    }
+    (text, annotations)
+}
+
+/// Returns the repeat count of a `$*N` marker in `name`, if any.
+fn parse_repeater(name: &str, name_span: Span) -> Result<Option<usize>, ParseError> {
+    let marker_index = match name.find(REPEATER_MARKER) {
+        Some(index) => index,
+        None => return Ok(None),
+    };
+    let digits: String = name[marker_index + REPEATER_MARKER.len()..]
+        .chars()
+        .take_while(|c| c.is_ascii_digit())
+        .collect();
+    let marker_column = name_span.column + name[..marker_index].chars().count();
+    let marker_span = Span::on_line(
+        name_span.line,
+        marker_column,
+        marker_column + REPEATER_MARKER.len() + digits.len(),
+    );
+
+    if name[marker_index + REPEATER_MARKER.len()..].contains(REPEATER_MARKER) {
+        return Err(ParseError::new(
+            "Only one repeater is allowed per name",
+            marker_span,
+        ));
+    }
+    match digits.parse::<usize>() {
+        Ok(count) if count > 0 => Ok(Some(count)),
+        _ => Err(ParseError::new(
+            "Repeater must be followed by a positive count, for example $*3",
+            marker_span,
+        )),
+    }
+}
+
+/// Last path component of a copy/move source, used as the default target name.
+pub fn source_file_name(source: &str) -> &str {
+    source
+        .trim_end_matches(['/', '\\'])
+        .rsplit(['/', '\\'])
+        .next()
+        .unwrap_or(source)
+}
+
+fn build_tree(flat: Vec<StructureNode>) -> Vec<StructureNode> {
+    let mut roots: Vec<StructureNode> = Vec::new();
+    // Stack of open ancestors; each node is attached to its parent when popped.
+    let mut stack: Vec<StructureNode> = Vec::new();
+
+    for node in flat {
+        while stack.last().is_some_and(|open| open.level >= node.level) {
+            attach(stack.pop().unwrap(), &mut stack, &mut roots);
+        }
+        stack.push(node);
+    }
+    while let Some(open) = stack.pop() {
+        attach(open, &mut stack, &mut roots);
+    }
+    roots
+}
+
+fn attach(node: StructureNode, stack: &mut [StructureNode], roots: &mut Vec<StructureNode>) {
+    match stack.last_mut() {
+        Some(parent) => parent.children.push(node),
+        None => roots.push(node),
+    }
+}
+
+/// Plain entries are folders when they have children, end with `/` or have no
+/// extension; everything else is a file. Entries with content or `!file` are
+/// always files, and such entries cannot have children.
+fn finalize_kinds(nodes: &mut [StructureNode]) -> Result<(), ParseError> {
+    for node in nodes.iter_mut() {
+        if node.kind == NodeKind::File || node.kind == NodeKind::Folder {
+            let explicit_folder = node.name.ends_with('/');
+            if explicit_folder {
+                node.name = node.name.trim_end_matches('/').to_string();
+            }
+            let forced_file = node.content.is_some() || node.explicit_file;
+            if forced_file && !node.children.is_empty() {
+                return Err(ParseError::new(
+                    "A file cannot contain other entries",
+                    node.children[0].span,
+                ));
+            }
+            node.kind = if forced_file {
+                NodeKind::File
+            } else if explicit_folder || !node.children.is_empty() || !has_extension(&node.name) {
+                NodeKind::Folder
+            } else {
+                NodeKind::File
+            };
+        }
+        finalize_kinds(&mut node.children)?;
+    }
+    Ok(())
+}
+
+fn has_extension(name: &str) -> bool {
+    std::path::Path::new(name).extension().is_some()
+}
+
+/// Expands a `$*N` repeater for the 1-based `index`.
+pub fn repeated_name(name: &str, index: usize) -> String {
+    match name.find(REPEATER_MARKER) {
+        Some(marker_index) => {
+            let digits_len = name[marker_index + REPEATER_MARKER.len()..]
+                .chars()
+                .take_while(|c| c.is_ascii_digit())
+                .count();
+            format!(
+                "{}{}{}",
+                &name[..marker_index],
+                index,
+                &name[marker_index + REPEATER_MARKER.len() + digits_len..]
+            )
+        }
+        None => name.to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse_error(text: &str) -> ParseError {
+        parse(text).expect_err("structure should not parse")
+    }
+
+    #[test]
+    fn spaces_in_indentation_are_reported_up_to_the_entry() {
+        let error = parse_error("project\n\t  src");
+        assert_eq!(error.message, "Indentation must use tabs");
+        assert_eq!(error.span, Span::on_line(2, 1, 4));
+    }
+
+    #[test]
+    fn indentation_jumps_are_reported() {
+        let error = parse_error("\tproject");
+        assert_eq!(error.message, "The first entry cannot be indented");
+        assert_eq!(error.span, Span::on_line(1, 1, 2));
+
+        let error = parse_error("project\n\tsrc\n\t\t\tdeep");
+        assert_eq!(error.message, "Indentation jumps from level 1 to level 3");
+        assert_eq!(error.span, Span::on_line(3, 1, 4));
+    }
+
+    #[test]
+    fn copy_move_and_rename_lines() {
+        let document =
+            parse("project\n\t[~/Desktop/logo.png]\n\t(~/Downloads/notes.md) > readme.md").unwrap();
+        let children = &document.nodes[0].children;
+
+        assert_eq!(children[0].kind, NodeKind::Copy);
+        assert_eq!(children[0].name, "logo.png");
+        assert_eq!(children[0].source.as_deref(), Some("~/Desktop/logo.png"));
+        assert_eq!(children[0].source_span, Some(Span::on_line(2, 3, 21)));
+        assert_eq!(children[0].name_span, Span::on_line(2, 13, 21));
+
+        assert_eq!(children[1].kind, NodeKind::Move);
+        assert_eq!(children[1].name, "readme.md");
+        assert_eq!(children[1].source.as_deref(), Some("~/Downloads/notes.md"));
+        assert_eq!(children[1].name_span, Span::on_line(3, 27, 36));
+    }
+
+    #[test]
+    fn malformed_copy_lines_are_rejected() {
+        assert_eq!(parse_error("[~/logo.png").message, "Missing closing ']'");
+        assert_eq!(parse_error("[ ]").message, "Source path cannot be empty");
+        assert_eq!(
+            parse_error("(notes.md) >").message,
+            "Missing name after '>'"
+        );
+        assert_eq!(
+            parse_error("[logo.png] extra").message,
+            "Unexpected text after ']'"
+        );
+    }
+
+    #[test]
+    fn repeaters_need_one_positive_count() {
+        let document = parse("chapter_$*3").unwrap();
+        assert_eq!(document.nodes[0].repeat, Some(3));
+        assert_eq!(repeated_name("chapter_$*3", 2), "chapter_2");
+
+        let error = parse_error("\nchapter_$*0");
+        assert_eq!(
+            error.message,
+            "Repeater must be followed by a positive count, for example $*3"
+        );
+        assert_eq!(error.span, Span::on_line(2, 9, 12));
+
+        let error = parse_error("part_$*2_$*3");
+        assert_eq!(error.message, "Only one repeater is allowed per name");
+        assert_eq!(error.span, Span::on_line(1, 6, 9));
+
+        assert!(parse("chapter_$*").is_err());
+    }
+
+    #[test]
+    fn frontmatter_is_split_from_the_body() {
+        let document = parse("---\nvariables:\n  name: demo\n---\nproject").unwrap();
+        let frontmatter = document.frontmatter.unwrap();
+        assert_eq!(frontmatter.raw, "variables:\n  name: demo");
+        assert_eq!(frontmatter.span.end_line, 4);
+        assert_eq!(document.nodes[0].name, "project");
+        assert_eq!(document.nodes[0].span.line, 5);
+    }
+
+    #[test]
+    fn unterminated_frontmatter_is_rejected() {
+        let error = parse_error("---\nvariables:\n  name: demo\nproject");
+        assert_eq!(error.message, "Frontmatter block is not closed with '---'");
+        assert_eq!(error.span, Span::on_line(1, 1, 4));
+        assert!(split_frontmatter("---\n").is_err());
+    }
+
+    #[test]
+    fn unterminated_content_blocks_are_rejected() {
+        let document = parse("project\n\t.gitignore <<EOF\n\t\tnode_modules\n\tEOF").unwrap();
+        let file = &document.nodes[0].children[0];
+        assert_eq!(file.content.as_deref(), Some("node_modules\n"));
+
+        let error = parse_error("project\n\t.gitignore <<EOF\n\t\tnode_modules");
+        assert_eq!(error.message, "Content block is not closed with 'EOF'");
+        assert_eq!(error.span.line, 2);
+    }
+
+    #[test]
+    fn file_marker_makes_extensionless_names_files() {
+        let document = parse("project\n\tLICENSE !file\n\tMakefile !file !skip\n\tbin").unwrap();
+        let children = &document.nodes[0].children;
+        assert_eq!(children[0].kind, NodeKind::File);
+        assert_eq!(children[0].name, "LICENSE");
+        assert_eq!(children[1].kind, NodeKind::File);
+        assert_eq!(children[1].name, "Makefile");
+        assert_eq!(children[1].conflict_policy, Some(ConflictPolicy::Skip));
+        assert_eq!(children[2].kind, NodeKind::Folder);
+
+        let document = parse("Dockerfile !skip !file <<EOF\n\tFROM scratch\nEOF").unwrap();
+        assert_eq!(document.nodes[0].kind, NodeKind::File);
+        assert_eq!(document.nodes[0].content.as_deref(), Some("FROM scratch\n"));
+        assert_eq!(
+            content_delimiter("Dockerfile !skip !file <<EOF"),
+            Some("EOF")
+        );
+        assert_eq!(content_delimiter("Dockerfile <<EOF !file"), Some("EOF"));
+    }
+
+    #[test]
+    fn file_marker_is_only_valid_on_plain_names() {
+        assert_eq!(
+            parse_error("LICENSE !file\n\tsrc").message,
+            "A file cannot contain other entries"
+        );
+        assert_eq!(
+            parse_error("docs/ !file").message,
+            "Only plain names can be marked with !file"
+        );
+        assert_eq!(
+            parse_error("[~/LICENSE] !file").message,
+            "Only plain names can be marked with !file"
+        );
+        // A lone annotation is a name, not a marker.
+        assert_eq!(parse("!file").unwrap().nodes[0].name, "!file");
+    }
+}
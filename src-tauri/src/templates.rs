@@ -0,0 +1,669 @@
+// Template files, their categories and their frontmatter.
+//
+// Templates are `.txt` structures in the templates folder; subfolders are
+// categories and may nest. Folders starting with a dot are not categories.
+// Besides the personal templates folder, further sources such as a shared
+// team library can be configured; see `load_sources`. Their frontmatter is
+// read here into typed fields so the frontend does not have to re-parse it:
+//
+//   ---
+//   order: 1
+//   description: React app with pages and components
+//   tags: [web, react]
+//   icon: globe
+//   author: File Architect
+//   version: 1.0.0
+//   defaultDestination: ~/Projects
+//   variables: …        (see `variables.rs`)
+//   ---
+
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+
+use crate::revisions;
+use crate::structure;
+use crate::variables::{self, TemplateVariable};
+
+pub const TEMPLATE_EXTENSION: &str = "txt";
+/// The source backed by the templates folder in the data root.
+pub const PERSONAL_SOURCE: &str = "Personal";
+const MAX_NAME_LENGTH: usize = 200;
+const FORBIDDEN_CHARACTERS: [char; 9] = ['/', '\\', ':', '*', '?', '"', '<', '>', '|'];
+/// Device names Windows refuses as file names, with or without an extension.
+pub(crate) const RESERVED_NAMES: [&str; 22] = [
+    "CON", "PRN", "AUX", "NUL", "COM1", "COM2", "COM3", "COM4", "COM5", "COM6", "COM7", "COM8",
+    "COM9", "LPT1", "LPT2", "LPT3", "LPT4", "LPT5", "LPT6", "LPT7", "LPT8", "LPT9",
+];
+
+// -----------------
+// Types
+// -----------------
+#[derive(Debug, Clone, Default, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TemplateMetadata {
+    pub order: Option<f64>,
+    pub description: Option<String>,
+    pub tags: Vec<String>,
+    pub icon: Option<String>,
+    pub author: Option<String>,
+    pub version: Option<String>,
+    pub default_destination: Option<String>,
+    pub variables: Vec<TemplateVariable>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Template {
+    pub name: String,
+    /// Category path such as `Work/Clients`; `None` for the top level.
+    pub category: Option<String>,
+    pub content: String,
+    #[serde(flatten)]
+    pub metadata: TemplateMetadata,
+    /// Frontmatter problems; the fields concerned keep their defaults.
+    pub errors: Vec<String>,
+    /// Name of the template source it was loaded from.
+    pub source: String,
+    pub read_only: bool,
+}
+
+/// A folder of templates. Earlier sources take precedence: a template with
+/// the same category and name in a later source is hidden.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TemplateSource {
+    pub name: String,
+    pub path: String,
+    #[serde(default = "read_only_by_default")]
+    pub read_only: bool,
+}
+
+/// Shared libraries are usually maintained elsewhere.
+fn read_only_by_default() -> bool {
+    true
+}
+
+/// A template named by its category and name, whichever source it is in.
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TemplateRef {
+    pub name: String,
+    pub category: Option<String>,
+}
+
+/// A template file that could not be read at all.
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TemplateError {
+    pub name: String,
+    pub path: String,
+    pub message: String,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TemplateCategory {
+    pub name: String,
+    pub path: String,
+    pub templates: Vec<Template>,
+    pub categories: Vec<TemplateCategory>,
+}
+
+/// The templates folder as a tree: top-level templates plus categories.
+#[derive(Debug, Clone, Default, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TemplateListing {
+    pub templates: Vec<Template>,
+    pub categories: Vec<TemplateCategory>,
+    pub errors: Vec<TemplateError>,
+}
+
+// -----------------
+// Frontmatter
+// -----------------
+
+/// Reads the typed fields of a template's frontmatter. Each invalid field is
+/// reported on its own and left at its default.
+pub fn parse_metadata(content: &str) -> (TemplateMetadata, Vec<String>) {
+    let mut metadata = TemplateMetadata::default();
+    let mut errors = Vec::new();
+
+    let frontmatter = match structure::split_frontmatter(content) {
+        Ok((Some(frontmatter), _)) => frontmatter.raw,
+        Ok((None, _)) => return (metadata, errors),
+        Err(error) => {
+            errors.push(error.to_string());
+            return (metadata, errors);
+        }
+    };
+    if frontmatter.trim().is_empty() {
+        return (metadata, errors);
+    }
+    let fields = match serde_yaml::from_str::<serde_yaml::Value>(&frontmatter) {
+        Ok(serde_yaml::Value::Mapping(fields)) => fields,
+        Ok(serde_yaml::Value::Null) => return (metadata, errors),
+        Ok(_) => {
+            errors.push("Frontmatter must be a list of 'key: value' fields".to_string());
+            return (metadata, errors);
+        }
+        Err(error) => {
+            errors.push(format!("Invalid frontmatter: {}", error));
+            return (metadata, errors);
+        }
+    };
+
+    for (key, value) in &fields {
+        let Some(key) = key.as_str() else {
+            continue;
+        };
+        let result = match key {
+            "order" => value
+                .as_f64()
+                .map(|order| metadata.order = Some(order))
+                .ok_or("must be a number"),
+            "description" => text(value).map(|text| metadata.description = Some(text)),
+            "icon" => text(value).map(|text| metadata.icon = Some(text)),
+            "author" => text(value).map(|text| metadata.author = Some(text)),
+            "version" => text(value).map(|text| metadata.version = Some(text)),
+            "defaultDestination" | "default_destination" => {
+                text(value).map(|text| metadata.default_destination = Some(text))
+            }
+            "tags" => tags(value).map(|tags| metadata.tags = tags),
+            "variables" => match variables::variables_from_yaml(value) {
+                Ok(variables) => {
+                    metadata.variables = variables;
+                    Ok(())
+                }
+                Err(error) => {
+                    errors.push(error);
+                    continue;
+                }
+            },
+            // Unknown keys are left for other tools.
+            _ => Ok(()),
+        };
+        if let Err(problem) = result {
+            errors.push(format!("'{}' {}", key, problem));
+        }
+    }
+    (metadata, errors)
+}
+
+fn text(value: &serde_yaml::Value) -> Result<String, &'static str> {
+    variables::scalar_string(value).ok_or("must be text")
+}
+
+/// Tags may be a YAML list or a comma-separated string.
+fn tags(value: &serde_yaml::Value) -> Result<Vec<String>, &'static str> {
+    let tags: Vec<String> = match value {
+        serde_yaml::Value::Sequence(items) => items
+            .iter()
+            .map(|item| variables::scalar_string(item).ok_or("must only contain text"))
+            .collect::<Result<_, _>>()?,
+        serde_yaml::Value::String(list) => list.split(',').map(str::to_string).collect(),
+        serde_yaml::Value::Null => Vec::new(),
+        _ => return Err("must be a list"),
+    };
+    Ok(tags
+        .into_iter()
+        .map(|tag| tag.trim().to_string())
+        .filter(|tag| !tag.is_empty())
+        .collect())
+}
+
+// -----------------
+// Loading
+// -----------------
+
+/// Reads every template below `root`, each folder sorted by `order` and then
+/// by name.
+pub fn load_templates(root: &Path) -> TemplateListing {
+    let mut errors = Vec::new();
+    let (templates, categories) = load_folder(root, None, &mut errors);
+    TemplateListing {
+        templates,
+        categories,
+        errors,
+    }
+}
+
+fn load_folder(
+    dir: &Path,
+    category: Option<&str>,
+    errors: &mut Vec<TemplateError>,
+) -> (Vec<Template>, Vec<TemplateCategory>) {
+    let mut templates = Vec::new();
+    let mut categories = Vec::new();
+    let Ok(entries) = fs::read_dir(dir) else {
+        return (templates, categories);
+    };
+
+    for entry in entries.flatten() {
+        let path = entry.path();
+        let file_name = entry.file_name().to_string_lossy().into_owned();
+        if path.is_dir() {
+            if file_name.starts_with('.') {
+                continue;
+            }
+            let category_path = match category {
+                Some(parent) => format!("{}/{}", parent, file_name),
+                None => file_name.clone(),
+            };
+            let (templates, children) = load_folder(&path, Some(&category_path), errors);
+            categories.push(TemplateCategory {
+                name: file_name,
+                path: category_path,
+                templates,
+                categories: children,
+            });
+            continue;
+        }
+        if path
+            .extension()
+            .map_or(true, |ext| ext != TEMPLATE_EXTENSION)
+        {
+            continue;
+        }
+        let name = path
+            .file_stem()
+            .map(|stem| stem.to_string_lossy().into_owned())
+            .unwrap_or_else(|| "Unnamed Template".to_string());
+        match fs::read_to_string(&path) {
+            Ok(content) => {
+                templates.push(load_template(name, category.map(str::to_string), content))
+            }
+            Err(error) => errors.push(TemplateError {
+                name,
+                path: path.to_string_lossy().into_owned(),
+                message: error.to_string(),
+            }),
+        }
+    }
+
+    sort_folder(&mut templates, &mut categories);
+    (templates, categories)
+}
+
+pub fn load_template(name: String, category: Option<String>, content: String) -> Template {
+    let (metadata, errors) = parse_metadata(&content);
+    Template {
+        name,
+        category,
+        content,
+        metadata,
+        errors,
+        source: PERSONAL_SOURCE.to_string(),
+        read_only: false,
+    }
+}
+
+/// Reads every source and merges them into one tree. A source folder that is
+/// missing, e.g. an unmounted network drive, is reported in `errors`.
+pub fn load_sources(sources: &[TemplateSource]) -> TemplateListing {
+    let mut listing = TemplateListing::default();
+    for source in sources {
+        let path = crate::expand_tilde_path(&source.path);
+        if !path.is_dir() {
+            listing.errors.push(TemplateError {
+                name: source.name.clone(),
+                path: path.to_string_lossy().into_owned(),
+                message: format!("Template source '{}' is not available", source.name),
+            });
+            continue;
+        }
+        let mut loaded = load_templates(&path);
+        mark_source(&mut loaded.templates, &mut loaded.categories, source);
+        merge_templates(&mut listing.templates, loaded.templates);
+        merge_categories(&mut listing.categories, loaded.categories);
+        listing.errors.extend(loaded.errors);
+    }
+    sort_folder(&mut listing.templates, &mut listing.categories);
+    listing
+}
+
+fn mark_source(
+    templates: &mut [Template],
+    categories: &mut [TemplateCategory],
+    source: &TemplateSource,
+) {
+    for template in templates {
+        template.source = source.name.clone();
+        template.read_only = source.read_only;
+    }
+    for category in categories {
+        mark_source(&mut category.templates, &mut category.categories, source);
+    }
+}
+
+fn merge_templates(into: &mut Vec<Template>, templates: Vec<Template>) {
+    for template in templates {
+        if !into.iter().any(|known| known.name == template.name) {
+            into.push(template);
+        }
+    }
+}
+
+fn merge_categories(into: &mut Vec<TemplateCategory>, categories: Vec<TemplateCategory>) {
+    for category in categories {
+        match into.iter_mut().find(|known| known.name == category.name) {
+            Some(known) => {
+                merge_templates(&mut known.templates, category.templates);
+                merge_categories(&mut known.categories, category.categories);
+            }
+            None => into.push(category),
+        }
+    }
+}
+
+fn sort_folder(templates: &mut [Template], categories: &mut [TemplateCategory]) {
+    templates.sort_by(|a, b| {
+        let order = |template: &Template| template.metadata.order.unwrap_or(f64::INFINITY);
+        order(a)
+            .total_cmp(&order(b))
+            .then_with(|| a.name.cmp(&b.name))
+    });
+    categories.sort_by(|a, b| a.name.cmp(&b.name));
+    for category in categories {
+        sort_folder(&mut category.templates, &mut category.categories);
+    }
+}
+
+// -----------------
+// Naming
+// -----------------
+
+/// Checks that `name` can be used as a template or category name on every
+/// platform and cannot point outside its folder.
+pub fn check_name(name: &str) -> Result<(), String> {
+    let invalid = |reason: &str| Err(format!("Invalid name '{}': {}", name, reason));
+    if name.trim().is_empty() {
+        return invalid("it is empty");
+    }
+    if name.chars().count() > MAX_NAME_LENGTH {
+        return invalid("it is too long");
+    }
+    if name.contains(FORBIDDEN_CHARACTERS) || name.chars().any(char::is_control) {
+        return invalid("it cannot contain / \\ : * ? \" < > | or control characters");
+    }
+    if name.starts_with('.') {
+        return invalid("it cannot start with a dot");
+    }
+    if name.ends_with(['.', ' ']) || name.starts_with(' ') {
+        return invalid("it cannot start or end with a space or end with a dot");
+    }
+    let stem = name.split('.').next().unwrap_or(name).trim_end();
+    if RESERVED_NAMES
+        .iter()
+        .any(|reserved| reserved.eq_ignore_ascii_case(stem))
+    {
+        return invalid("it is reserved by Windows");
+    }
+    Ok(())
+}
+
+/// Turns arbitrary text into a valid name by replacing what [`check_name`]
+/// rejects, e.g. for names taken from imported files.
+pub fn sanitize_name(name: &str) -> String {
+    let replaced: String = name
+        .chars()
+        .map(|c| {
+            if FORBIDDEN_CHARACTERS.contains(&c) || c.is_control() {
+                '-'
+            } else {
+                c
+            }
+        })
+        .take(MAX_NAME_LENGTH)
+        .collect();
+    let mut sanitized = replaced
+        .trim_matches(|c: char| c == ' ' || c == '.')
+        .to_string();
+    if sanitized.is_empty() {
+        sanitized = "Untitled".to_string();
+    }
+    if check_name(&sanitized).is_err() {
+        sanitized.insert(0, '_');
+    }
+    sanitized
+}
+
+// -----------------
+// Categories
+// -----------------
+
+/// Resolves a category path like `Work/Clients` below `root`. `None` or an
+/// empty path is the top level.
+pub fn category_dir(root: &Path, category: Option<&str>) -> Result<PathBuf, String> {
+    let mut dir = root.to_path_buf();
+    for segment in category
+        .unwrap_or_default()
+        .split(['/', '\\'])
+        .filter(|segment| !segment.is_empty())
+    {
+        check_category_name(segment)?;
+        dir.push(segment);
+    }
+    Ok(dir)
+}
+
+fn check_category_name(name: &str) -> Result<(), String> {
+    check_name(name).map_err(|error| format!("Category: {}", error))
+}
+
+pub fn template_path(root: &Path, category: Option<&str>, name: &str) -> Result<PathBuf, String> {
+    check_name(name)?;
+    Ok(category_dir(root, category)?.join(format!("{}.{}", name, TEMPLATE_EXTENSION)))
+}
+
+pub fn create_category(root: &Path, category: &str) -> Result<(), String> {
+    let dir = category_dir(root, Some(category))?;
+    if dir == root {
+        return Err("Category name cannot be empty".to_string());
+    }
+    if dir.exists() {
+        return Err(format!("Category already exists: {}", category));
+    }
+    fs::create_dir_all(&dir).map_err(|e| e.to_string())
+}
+
+/// Renames the last segment of a category, keeping it under the same parent.
+pub fn rename_category(root: &Path, category: &str, new_name: &str) -> Result<(), String> {
+    check_category_name(new_name)?;
+    let dir = category_dir(root, Some(category))?;
+    if dir == root || !dir.is_dir() {
+        return Err(format!("Category does not exist: {}", category));
+    }
+    let renamed = dir.with_file_name(new_name);
+    if renamed.exists() {
+        return Err(format!("Category already exists: {}", new_name));
+    }
+    fs::rename(&dir, &renamed).map_err(|e| e.to_string())?;
+    let category = category.trim_matches(['/', '\\']);
+    let renamed_category = match category.rsplit_once(['/', '\\']) {
+        Some((parent, _)) => format!("{}/{}", parent, new_name),
+        None => new_name.to_string(),
+    };
+    revisions::move_category_history(root, category, &renamed_category)
+}
+
+// -----------------
+// Templates
+// -----------------
+
+/// Whether both paths name the same existing file or folder, such as one
+/// spelled with a different case on a case-insensitive file system.
+pub(crate) fn is_same_file(a: &Path, b: &Path) -> bool {
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::MetadataExt;
+        matches!(
+            (fs::metadata(a), fs::metadata(b)),
+            (Ok(a), Ok(b)) if a.dev() == b.dev() && a.ino() == b.ino()
+        )
+    }
+    #[cfg(not(unix))]
+    {
+        matches!(
+            (a.canonicalize(), b.canonicalize()),
+            (Ok(a), Ok(b)) if a == b
+        )
+    }
+}
+
+/// Writes the whole file next to `path` first and renames it into place, so
+/// an interrupted save leaves either the old or the new content.
+pub fn write_atomic(path: &Path, content: &[u8]) -> io::Result<()> {
+    let file_name = path
+        .file_name()
+        .map(|name| name.to_string_lossy().into_owned())
+        .unwrap_or_default();
+    let temporary = path.with_file_name(format!(
+        ".{}.tmp-{}",
+        file_name,
+        uuid::Uuid::new_v4().simple()
+    ));
+    let written = (|| {
+        let mut file = fs::File::create(&temporary)?;
+        file.write_all(content)?;
+        file.sync_all()?;
+        fs::rename(&temporary, path)
+    })();
+    if written.is_err() {
+        let _ = fs::remove_file(&temporary);
+    }
+    written
+}
+
+pub fn save_template(
+    root: &Path,
+    category: Option<&str>,
+    name: &str,
+    content: &str,
+) -> Result<(), String> {
+    let path = template_path(root, category, name)?;
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent).map_err(|e| e.to_string())?;
+    }
+    revisions::record(root, category, name, Some(content))?;
+    write_atomic(&path, content.as_bytes()).map_err(|e| e.to_string())
+}
+
+pub fn rename_template(
+    root: &Path,
+    category: Option<&str>,
+    name: &str,
+    new_name: &str,
+) -> Result<(), String> {
+    let source = existing_template(root, category, name)?;
+    let target = template_path(root, category, new_name)?;
+    // A change of case only finds the template itself on case-insensitive
+    // file systems; anywhere else an existing target is another template.
+    if target.exists() && !is_same_file(&source, &target) {
+        return Err(format!("A template named '{}' already exists", new_name));
+    }
+    fs::rename(&source, &target).map_err(|e| e.to_string())?;
+    revisions::move_history(root, (category, name), (category, new_name))
+}
+
+/// Copies a template within its category. Without `new_name` the copy is
+/// called `Name copy`, `Name copy 2`, … Returns the name used.
+pub fn duplicate_template(
+    root: &Path,
+    category: Option<&str>,
+    name: &str,
+    new_name: Option<&str>,
+) -> Result<String, String> {
+    let source = existing_template(root, category, name)?;
+    let new_name = match new_name {
+        Some(new_name) => new_name.to_string(),
+        None => {
+            let base = sanitize_name(&format!("{} copy", name));
+            (1..)
+                .map(|index| match index {
+                    1 => base.clone(),
+                    _ => format!("{} {}", base, index),
+                })
+                .find(|candidate| {
+                    template_path(root, category, candidate).is_ok_and(|path| !path.exists())
+                })
+                .unwrap_or(base)
+        }
+    };
+    let target = template_path(root, category, &new_name)?;
+    if target.exists() {
+        return Err(format!("A template named '{}' already exists", new_name));
+    }
+    let content = fs::read(&source).map_err(|e| e.to_string())?;
+    write_atomic(&target, &content).map_err(|e| e.to_string())?;
+    Ok(new_name)
+}
+
+/// Deletes a template along with its revision history.
+pub fn delete_template(root: &Path, category: Option<&str>, name: &str) -> Result<(), String> {
+    let path = existing_template(root, category, name)?;
+    fs::remove_file(path).map_err(|e| e.to_string())?;
+    revisions::delete_history(root, category, name)
+}
+
+fn existing_template(root: &Path, category: Option<&str>, name: &str) -> Result<PathBuf, String> {
+    let path = template_path(root, category, name)?;
+    if path.is_file() {
+        Ok(path)
+    } else {
+        Err(format!("Template does not exist: {}", name))
+    }
+}
+
+/// Moves a template to another category, which is created when missing.
+pub fn move_template(
+    root: &Path,
+    name: &str,
+    category: Option<&str>,
+    target_category: Option<&str>,
+) -> Result<(), String> {
+    let source = existing_template(root, category, name)?;
+    let target = template_path(root, target_category, name)?;
+    if target == source {
+        return Ok(());
+    }
+    if target.exists() {
+        return Err(format!(
+            "A template named '{}' already exists in {}",
+            name,
+            target_category.unwrap_or("the top level")
+        ));
+    }
+    if let Some(parent) = target.parent() {
+        fs::create_dir_all(parent).map_err(|e| e.to_string())?;
+    }
+    fs::rename(&source, &target).map_err(|e| e.to_string())?;
+    revisions::move_history(root, (category, name), (target_category, name))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn case_only_rename_does_not_replace_another_template() {
+        let dir = tempfile::tempdir().unwrap();
+        let root = dir.path();
+        save_template(root, None, "web", "lower").unwrap();
+        let upper = template_path(root, None, "Web").unwrap();
+        if upper.exists() {
+            // Case-insensitive file system: `Web` is `web` itself.
+            rename_template(root, None, "web", "Web").unwrap();
+            return;
+        }
+        save_template(root, None, "Web", "upper").unwrap();
+
+        let error = rename_template(root, None, "web", "Web").unwrap_err();
+        assert_eq!(error, "A template named 'Web' already exists");
+        assert_eq!(fs::read_to_string(upper).unwrap(), "upper");
+        rename_template(root, None, "web", "WEB").unwrap();
+        let renamed = template_path(root, None, "WEB").unwrap();
+        assert_eq!(fs::read_to_string(renamed).unwrap(), "lower");
+    }
+}
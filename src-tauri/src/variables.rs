@@ -0,0 +1,425 @@
+// `{{variable}}` placeholders in structure text.
+//
+// Variables are declared in the frontmatter with an optional type, default and
+// description, and are substituted into entry names and file content alike:
+//
+//   ---
+//   variables:
+//     className:
+//       description: Name of the class
+//       default: Math
+//     weeks:
+//       type: number
+//       default: 12
+//   ---
+//   {{className}}
+//   	week_$*12
+//   	syllabus.md <<EOF
+//   	# {{className}} ({{weeks}} weeks)
+//   	EOF
+
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+use crate::conditions;
+use crate::structure;
+
+const OPEN: &str = "{{";
+const CLOSE: &str = "}}";
+
+// -----------------
+// Types
+// -----------------
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum VariableType {
+    #[default]
+    String,
+    Number,
+    Boolean,
+    /// One of the declared `options`.
+    Choice,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TemplateVariable {
+    pub name: String,
+    #[serde(rename = "type")]
+    pub variable_type: VariableType,
+    pub default: Option<String>,
+    pub description: Option<String>,
+    pub options: Vec<String>,
+    /// False for placeholders used in the body without a declaration.
+    pub declared: bool,
+}
+
+/// A frontmatter declaration; a bare scalar is shorthand for its default.
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+struct VariableDeclaration {
+    #[serde(rename = "type")]
+    variable_type: Option<VariableType>,
+    default: Option<serde_yaml::Value>,
+    description: Option<String>,
+    options: Vec<serde_yaml::Value>,
+}
+
+// -----------------
+// Discovery
+// -----------------
+
+/// The variables a structure needs: its declarations in order, then any
+/// undeclared placeholders in the order they first appear.
+pub fn template_variables(text: &str) -> Result<Vec<TemplateVariable>, String> {
+    let (frontmatter, body) = structure::split_frontmatter(text).map_err(|e| e.to_string())?;
+    let mut variables = match frontmatter {
+        Some(frontmatter) => declared_variables(&frontmatter.raw)?,
+        None => Vec::new(),
+    };
+    let used = placeholder_names(body)
+        .into_iter()
+        .map(|name| (name, false))
+        .chain(conditions::condition_names(body));
+    for (name, is_flag) in used {
+        if !variables.iter().any(|variable| variable.name == name) {
+            variables.push(TemplateVariable {
+                name,
+                variable_type: if is_flag {
+                    VariableType::Boolean
+                } else {
+                    VariableType::String
+                },
+                default: None,
+                description: None,
+                options: Vec::new(),
+                declared: false,
+            });
+        }
+    }
+    Ok(variables)
+}
+
+/// Reads the `variables` mapping of a frontmatter block.
+pub fn declared_variables(frontmatter: &str) -> Result<Vec<TemplateVariable>, String> {
+    if frontmatter.trim().is_empty() {
+        return Ok(Vec::new());
+    }
+    let value: serde_yaml::Value =
+        serde_yaml::from_str(frontmatter).map_err(|e| format!("Invalid frontmatter: {}", e))?;
+    match value.get("variables") {
+        Some(declarations) => variables_from_yaml(declarations),
+        None => Ok(Vec::new()),
+    }
+}
+
+/// Reads the value of an already parsed `variables` key.
+pub fn variables_from_yaml(
+    declarations: &serde_yaml::Value,
+) -> Result<Vec<TemplateVariable>, String> {
+    let declarations = match declarations {
+        serde_yaml::Value::Null => return Ok(Vec::new()),
+        serde_yaml::Value::Mapping(declarations) => declarations,
+        _ => return Err("Frontmatter 'variables' must be a mapping".to_string()),
+    };
+
+    let mut variables = Vec::with_capacity(declarations.len());
+    for (name, declaration) in declarations {
+        let name = scalar_string(name)
+            .filter(|name| is_valid_name(name))
+            .ok_or_else(|| format!("Invalid variable name: {:?}", name))?;
+        let declaration = match declaration {
+            serde_yaml::Value::Mapping(_) => {
+                serde_yaml::from_value::<VariableDeclaration>(declaration.clone())
+                    .map_err(|e| format!("Variable '{}': {}", name, e))?
+            }
+            scalar => VariableDeclaration {
+                default: Some(scalar.clone()),
+                ..Default::default()
+            },
+        };
+        let options: Vec<String> = declaration
+            .options
+            .iter()
+            .filter_map(scalar_string)
+            .collect();
+        let variable_type = match declaration.variable_type {
+            Some(variable_type) => variable_type,
+            None if !options.is_empty() => VariableType::Choice,
+            None => match declaration.default {
+                Some(serde_yaml::Value::Number(_)) => VariableType::Number,
+                Some(serde_yaml::Value::Bool(_)) => VariableType::Boolean,
+                _ => VariableType::String,
+            },
+        };
+        if variable_type == VariableType::Choice && options.is_empty() {
+            return Err(format!(
+                "Variable '{}': choices need a list of options",
+                name
+            ));
+        }
+        let variable = TemplateVariable {
+            default: declaration.default.as_ref().and_then(scalar_string),
+            name,
+            variable_type,
+            description: declaration.description,
+            options,
+            declared: true,
+        };
+        if let Some(default) = &variable.default {
+            check_value(&variable, default)?;
+        }
+        variables.push(variable);
+    }
+    Ok(variables)
+}
+
+pub(crate) fn scalar_string(value: &serde_yaml::Value) -> Option<String> {
+    match value {
+        serde_yaml::Value::String(text) => Some(text.clone()),
+        serde_yaml::Value::Number(number) => Some(number.to_string()),
+        serde_yaml::Value::Bool(flag) => Some(flag.to_string()),
+        _ => None,
+    }
+}
+
+fn is_valid_name(name: &str) -> bool {
+    let mut chars = name.chars();
+    chars
+        .next()
+        .is_some_and(|first| first.is_alphabetic() || first == '_')
+        && chars.all(|c| c.is_alphanumeric() || c == '_' || c == '-')
+}
+
+/// Names of the `{{name}}` placeholders in `text`, without duplicates.
+fn placeholder_names(text: &str) -> Vec<String> {
+    let mut names: Vec<String> = Vec::new();
+    for (_, name, _) in placeholders(text) {
+        if !names.iter().any(|known| known == name) {
+            names.push(name.to_string());
+        }
+    }
+    names
+}
+
+/// Every placeholder as `(start, name, end)` byte offsets into `text`.
+/// Braces that do not enclose a valid name are left alone.
+pub(crate) fn placeholders(text: &str) -> Vec<(usize, &str, usize)> {
+    let mut found = Vec::new();
+    let mut from = 0;
+    while let Some(open) = text[from..].find(OPEN).map(|index| from + index) {
+        let inner_start = open + OPEN.len();
+        let Some(close) = text[inner_start..]
+            .find(CLOSE)
+            .map(|index| inner_start + index)
+        else {
+            break;
+        };
+        let name = text[inner_start..close].trim();
+        if is_valid_name(name) && !text[inner_start..close].contains('\n') {
+            found.push((open, name, close + CLOSE.len()));
+            from = close + CLOSE.len();
+        } else {
+            from = inner_start;
+        }
+    }
+    found
+}
+
+// -----------------
+// Rendering
+// -----------------
+
+/// Drops the `@if` sections whose condition does not hold and substitutes
+/// placeholders in the body of a structure. Values fall back to the declared
+/// defaults; the frontmatter is kept as written. Undeclared
+/// placeholders without a value are left alone, so file content using `{{ }}`
+/// syntax of its own survives. Values substituted into entry lines, as opposed
+/// to content blocks, must pass [`check_name_value`].
+pub fn render(text: &str, values: &HashMap<String, String>) -> Result<String, String> {
+    let (_, body) = structure::split_frontmatter(text).map_err(|e| e.to_string())?;
+    let header = &text[..text.len() - body.len()];
+    let variables = template_variables(text)?;
+
+    let mut resolved: HashMap<&str, &str> = HashMap::new();
+    for variable in &variables {
+        let value = match values.get(&variable.name).or(variable.default.as_ref()) {
+            Some(value) => value,
+            None if !variable.declared => continue,
+            None => return Err(format!("Missing value for variable '{}'", variable.name)),
+        };
+        check_value(variable, value)?;
+        resolved.insert(&variable.name, value);
+    }
+
+    let first_line = header.matches('\n').count() + 1;
+    let body = conditions::apply(body, first_line, &|name| {
+        resolved.get(name).map(|value| value.to_string())
+    })?;
+    let body = body.as_str();
+
+    let mut rendered = String::with_capacity(text.len());
+    rendered.push_str(header);
+    let mut open_block: Option<String> = None;
+    for line in body.split_inclusive('\n') {
+        let text = line.trim_end_matches(['\n', '\r']);
+        let is_entry = match &open_block {
+            Some(delimiter) => {
+                if text.trim_start_matches('\t') == delimiter {
+                    open_block = None;
+                }
+                false
+            }
+            None => {
+                open_block = structure::content_delimiter(text).map(str::to_string);
+                true
+            }
+        };
+        let mut last = 0;
+        for (start, name, end) in placeholders(line) {
+            rendered.push_str(&line[last..start]);
+            match resolved.get(name) {
+                Some(value) => {
+                    if is_entry {
+                        check_name_value(name, value)?;
+                    }
+                    rendered.push_str(value);
+                }
+                None => rendered.push_str(&line[start..end]),
+            }
+            last = end;
+        }
+        rendered.push_str(&line[last..]);
+    }
+    Ok(rendered)
+}
+
+/// Text that changes how an entry line is read when it appears in a name.
+const NAME_SYNTAX: [&str; 7] = ["->", "=>", "<<", "$*", "{{", "}}", " !"];
+
+/// A value substituted into an entry line must stay part of a single name, so
+/// it cannot reach outside its folder or add structure syntax to the line.
+fn check_name_value(name: &str, value: &str) -> Result<(), String> {
+    let problem = if value.contains(['/', '\\']) {
+        "contain path separators"
+    } else if value.contains('\t') {
+        "contain tabs"
+    } else if matches!(value.trim(), "." | "..") {
+        "be '.' or '..'"
+    } else if value.starts_with(['[', '(', '@', '!'])
+        || NAME_SYNTAX.iter().any(|syntax| value.contains(syntax))
+    {
+        "contain structure syntax such as '->', '[...]' or '!skip'"
+    } else {
+        return Ok(());
+    };
+    Err(format!(
+        "Value for variable '{}' is used in a name and cannot {}: {}",
+        name, problem, value
+    ))
+}
+
+fn check_value(variable: &TemplateVariable, value: &str) -> Result<(), String> {
+    let valid = match variable.variable_type {
+        VariableType::String => true,
+        VariableType::Number => value.trim().parse::<f64>().is_ok(),
+        VariableType::Boolean => conditions::is_flag_value(value),
+        VariableType::Choice => variable.options.iter().any(|option| option == value),
+    };
+    if value.contains(['\n', '\r']) {
+        return Err(format!(
+            "Value for variable '{}' cannot contain line breaks",
+            variable.name
+        ));
+    }
+    if !valid {
+        return Err(format!(
+            "Invalid {} value for variable '{}': {}",
+            type_label(variable.variable_type),
+            variable.name,
+            value
+        ));
+    }
+    Ok(())
+}
+
+fn type_label(variable_type: VariableType) -> &'static str {
+    match variable_type {
+        VariableType::String => "text",
+        VariableType::Number => "number",
+        VariableType::Boolean => "boolean",
+        VariableType::Choice => "choice",
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn render_with(text: &str, name: &str, value: &str) -> Result<String, String> {
+        render(
+            text,
+            &HashMap::from([(name.to_string(), value.to_string())]),
+        )
+    }
+
+    #[test]
+    fn flags_accept_the_values_conditions_understand() {
+        let text = "project\n\t@if with_tests\n\ttests\n\t@endif";
+        let flag = &template_variables(text).unwrap()[0];
+        assert_eq!(flag.variable_type, VariableType::Boolean);
+
+        for value in ["no", "0", "", "False"] {
+            assert_eq!(render_with(text, "with_tests", value).unwrap(), "project\n");
+        }
+        for value in ["yes", "1", "TRUE"] {
+            assert_eq!(
+                render_with(text, "with_tests", value).unwrap(),
+                "project\n\ttests\n"
+            );
+        }
+        assert_eq!(
+            render_with(text, "with_tests", "maybe").unwrap_err(),
+            "Invalid boolean value for variable 'with_tests': maybe"
+        );
+    }
+
+    #[test]
+    fn values_in_names_cannot_escape_or_add_syntax() {
+        let text = "projects\n\t{{name}}\n\t\tnotes.md <<EOF\n\t\t\tSee {{name}}\n\t\tEOF";
+        for value in [
+            "../../etc",
+            "a/b",
+            "a\\b",
+            "..",
+            "x -> /target",
+            "x => y",
+            "[~/secret]",
+            "(~/Downloads)",
+            "x !skip",
+            "chapter_$*3",
+            "@include Web",
+            "a\tb",
+        ] {
+            let error = render_with(text, "name", value).unwrap_err();
+            assert!(
+                error.starts_with("Value for variable 'name' is used in a name"),
+                "{}: {}",
+                value,
+                error
+            );
+        }
+        assert_eq!(
+            render_with(text, "name", "Report (final) v1.2").unwrap(),
+            "projects\n\tReport (final) v1.2\n\t\tnotes.md <<EOF\n\t\t\tSee Report (final) v1.2\n\t\tEOF"
+        );
+
+        // Content blocks take any single-line value.
+        let text = "notes.md <<EOF\n\tpath: {{path}}\nEOF";
+        assert_eq!(
+            render_with(text, "path", "../a -> b").unwrap(),
+            "notes.md <<EOF\n\tpath: ../a -> b\nEOF"
+        );
+        assert!(render_with(text, "path", "a\nb").is_err());
+    }
+}
@@ -0,0 +1,254 @@
+// Watches the template sources and tells the frontend when templates change
+// outside the app, e.g. edited in another editor or synced by Dropbox.
+//
+// Raw file events are noisy (atomic saves, temporary files, sync clients), so
+// they only trigger a rescan: after a short quiet period the folders are listed
+// again and compared with the previous listing. A template found in several
+// sources counts as the one that takes precedence, the one the app shows.
+
+use notify::{Event, RecommendedWatcher, RecursiveMode, Watcher};
+use serde::Serialize;
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Component, Path, PathBuf};
+use std::sync::mpsc;
+use std::thread;
+use std::time::{Duration, SystemTime};
+use tauri::{AppHandle, Emitter, Runtime};
+
+use crate::templates::{TemplateRef, TEMPLATE_EXTENSION};
+
+pub const TEMPLATES_CHANGED_EVENT: &str = "templates-changed";
+/// Carries the reason the template sources could not be watched.
+pub const WATCH_FAILED_EVENT: &str = "templates-watch-failed";
+const QUIET_PERIOD: Duration = Duration::from_millis(300);
+
+// -----------------
+// Types
+// -----------------
+#[derive(Debug, Default, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TemplatesChanged {
+    pub added: Vec<TemplateRef>,
+    pub removed: Vec<TemplateRef>,
+    pub modified: Vec<TemplateRef>,
+}
+
+impl TemplatesChanged {
+    pub fn is_empty(&self) -> bool {
+        self.added.is_empty() && self.removed.is_empty() && self.modified.is_empty()
+    }
+}
+
+/// Stops watching when dropped.
+pub struct TemplatesWatcher {
+    _watcher: RecommendedWatcher,
+}
+
+type Snapshot = BTreeMap<TemplateRef, (Option<SystemTime>, u64)>;
+
+// -----------------
+// Snapshots
+// -----------------
+fn snapshot(template_dirs: &[PathBuf]) -> Snapshot {
+    let mut snapshot = Snapshot::new();
+    for dir in template_dirs {
+        snapshot_folder(dir, None, &mut snapshot);
+    }
+    snapshot
+}
+
+fn snapshot_folder(dir: &Path, category: Option<&str>, snapshot: &mut Snapshot) {
+    let Ok(entries) = fs::read_dir(dir) else {
+        return;
+    };
+    for entry in entries.flatten() {
+        let path = entry.path();
+        let file_name = entry.file_name().to_string_lossy().into_owned();
+        if file_name.starts_with('.') {
+            continue;
+        }
+        if path.is_dir() {
+            let category_path = match category {
+                Some(parent) => format!("{}/{}", parent, file_name),
+                None => file_name,
+            };
+            snapshot_folder(&path, Some(&category_path), snapshot);
+            continue;
+        }
+        if path
+            .extension()
+            .map_or(true, |ext| ext != TEMPLATE_EXTENSION)
+        {
+            continue;
+        }
+        let Some(name) = path.file_stem() else {
+            continue;
+        };
+        let stamp = entry
+            .metadata()
+            .map(|metadata| (metadata.modified().ok(), metadata.len()))
+            .unwrap_or((None, 0));
+        // Earlier sources take precedence
+        snapshot
+            .entry(TemplateRef {
+                name: name.to_string_lossy().into_owned(),
+                category: category.map(str::to_string),
+            })
+            .or_insert(stamp);
+    }
+}
+
+pub fn diff(before: &Snapshot, after: &Snapshot) -> TemplatesChanged {
+    let mut changes = TemplatesChanged::default();
+    for (template, stamp) in after {
+        match before.get(template) {
+            None => changes.added.push(template.clone()),
+            Some(previous) if previous != stamp => changes.modified.push(template.clone()),
+            Some(_) => {}
+        }
+    }
+    changes.removed = before
+        .keys()
+        .filter(|template| !after.contains_key(*template))
+        .cloned()
+        .collect();
+    changes
+}
+
+/// Revision history, state files and temporary files from atomic saves all
+/// live under dot-prefixed names and never change the template list by
+/// themselves.
+fn is_hidden(root: &Path, path: &Path) -> bool {
+    path.strip_prefix(root).is_ok_and(|relative| {
+        relative.components().any(|component| {
+            matches!(component, Component::Normal(name) if name.to_string_lossy().starts_with('.'))
+        })
+    })
+}
+
+// -----------------
+// Watching
+// -----------------
+
+/// Starts watching `template_dirs`, the folders of the template sources in
+/// order of precedence, and emits `templates-changed` with the added, removed
+/// and modified templates whenever they settle. Folders that do not exist are
+/// not watched.
+pub fn watch<R: Runtime>(
+    app: AppHandle<R>,
+    template_dirs: Vec<PathBuf>,
+) -> Result<TemplatesWatcher, String> {
+    // Listed before watching so changes made while the watcher starts are
+    // reported with the first rescan.
+    let mut known = snapshot(&template_dirs);
+
+    let (sender, receiver) = mpsc::channel();
+    let roots = template_dirs.clone();
+    let mut watcher = notify::recommended_watcher(move |result: notify::Result<Event>| {
+        if let Ok(event) = result {
+            let hidden = |path: &PathBuf| roots.iter().any(|root| is_hidden(root, path));
+            if event.paths.iter().any(|path| !hidden(path)) {
+                let _ = sender.send(());
+            }
+        }
+    })
+    .map_err(|e| e.to_string())?;
+    for dir in template_dirs.iter().filter(|dir| dir.is_dir()) {
+        watcher
+            .watch(dir, RecursiveMode::Recursive)
+            .map_err(|e| format!("{}: {}", dir.display(), e))?;
+    }
+
+    // The thread ends once the watcher, and with it the sender, is dropped.
+    thread::spawn(move || {
+        while receiver.recv().is_ok() {
+            while receiver.recv_timeout(QUIET_PERIOD).is_ok() {}
+            let current = snapshot(&template_dirs);
+            let changes = diff(&known, &current);
+            known = current;
+            if !changes.is_empty() {
+                let _ = app.emit(TEMPLATES_CHANGED_EVENT, &changes);
+            }
+        }
+    });
+
+    Ok(TemplatesWatcher { _watcher: watcher })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn template(category: Option<&str>, name: &str) -> TemplateRef {
+        TemplateRef {
+            name: name.to_string(),
+            category: category.map(str::to_string),
+        }
+    }
+
+    fn write(path: &Path, content: &str) {
+        fs::create_dir_all(path.parent().unwrap()).unwrap();
+        fs::write(path, content).unwrap();
+    }
+
+    #[test]
+    fn snapshots_list_templates_of_every_source() {
+        let dir = tempfile::tempdir().unwrap();
+        let personal = dir.path().join("personal");
+        let team = dir.path().join("team");
+        write(&personal.join("Web.txt"), "web");
+        write(&personal.join("Work/Clients/Site.txt"), "site");
+        write(&personal.join(".history/Web/1.txt"), "old");
+        write(&personal.join("notes.md"), "");
+        write(&team.join("Web.txt"), "team web");
+        write(&team.join("Api.txt"), "api");
+
+        let snapshot = snapshot(&[personal.clone(), team, dir.path().join("missing")]);
+        assert_eq!(
+            snapshot.keys().cloned().collect::<Vec<_>>(),
+            [
+                template(None, "Api"),
+                template(Some("Work/Clients"), "Site"),
+                template(None, "Web"),
+            ]
+        );
+        // The personal template hides the team one
+        assert_eq!(snapshot[&template(None, "Web")].1, 3);
+    }
+
+    #[test]
+    fn changes_are_reported_against_the_previous_snapshot() {
+        let dir = tempfile::tempdir().unwrap();
+        let personal = dir.path().join("personal");
+        let team = dir.path().join("team");
+        let dirs = [personal.clone(), team.clone()];
+        write(&personal.join("Web.txt"), "web");
+        write(&personal.join("Cli.txt"), "cli");
+        write(&team.join("Web.txt"), "team web");
+        let before = snapshot(&dirs);
+
+        fs::remove_file(personal.join("Cli.txt")).unwrap();
+        fs::remove_file(personal.join("Web.txt")).unwrap();
+        write(&team.join("Api.txt"), "api");
+        let changes = diff(&before, &snapshot(&dirs));
+        assert_eq!(changes.added, [template(None, "Api")]);
+        assert_eq!(changes.removed, [template(None, "Cli")]);
+        // The team template shows through once the personal one is gone
+        assert_eq!(changes.modified, [template(None, "Web")]);
+        assert!(diff(&before, &before).is_empty());
+    }
+
+    #[test]
+    fn dot_folders_and_files_are_hidden() {
+        let root = Path::new("/data/Templates");
+        assert!(is_hidden(root, &root.join(".history/Web/1.txt")));
+        assert!(is_hidden(root, &root.join("Work/.Web.txt.tmp")));
+        assert!(!is_hidden(root, &root.join("Work/Web.txt")));
+        // Only names inside the root count
+        assert!(!is_hidden(
+            Path::new("/data/.config/Templates"),
+            Path::new("/data/.config/Templates/Web.txt")
+        ));
+    }
+}
@@ -0,0 +1,33 @@
+import { invoke } from "@tauri-apps/api/core";
+import { documentDir, join } from "@tauri-apps/api/path";
+
+/**
+ * Where templates, blank files and run history live. The backend resolves it
+ * from FILEARCHITECT_DATA_DIR, the workspace's .filearchitect.json, the saved
+ * setting, or Documents/FileArchitect.
+ */
+export interface DataLocation {
+  root: string;
+  source: "environment" | "workspace" | "settings" | "default";
+  workspace: string | null;
+  templatesDir: string;
+  blankFilesDir: string;
+  historyDir: string;
+}
+
+export const getDataLocation = async (): Promise<DataLocation> => {
+  try {
+    return await invoke<DataLocation>("get_data_location");
+  } catch {
+    // Outside the desktop app (tests, browser preview) use the default layout
+    const root = await join(await documentDir(), "FileArchitect");
+    return {
+      root,
+      source: "default",
+      workspace: null,
+      templatesDir: await join(root, "Templates"),
+      blankFilesDir: await join(root, "BlankFiles"),
+      historyDir: await join(root, "History"),
+    };
+  }
+};
@@ -1,8 +1,9 @@
+import { getDataLocation } from "@/api/dataLocation";
 import { getStoreValue } from "@/api/store";
 import type { FileNameReplacement } from "@filearchitect/core";
 import { getStructure } from "@filearchitect/core";
 import { invoke } from "@tauri-apps/api/core";
-import { desktopDir, documentDir, extname, join } from "@tauri-apps/api/path";
+import { desktopDir, extname, join } from "@tauri-apps/api/path";
 import { fetch } from "@tauri-apps/plugin-http";
 import fs from "./fs";
 
@@ -55,8 +56,7 @@ export interface CreateFoldersExecutionResult {
 }
 
 async function getBlankFilesDir(): Promise<string> {
-  const documentsDir = await documentDir();
-  const blankFilesDir = await join(documentsDir, "FileArchitect", "BlankFiles");
+  const { blankFilesDir } = await getDataLocation();
   await fs.mkdir(blankFilesDir, { recursive: true });
   return blankFilesDir;
 }
@@ -1,3 +1,4 @@
+import { getDataLocation } from "@/api/dataLocation";
 import { Button } from "@/components/ui/button";
 import {
   ContextMenu,
@@ -370,11 +371,9 @@ const StructureManager: React.FC = () => {
   // Reveal a specific structure file in Finder
   const handleRevealStructure = async (structureName: string) => {
     try {
-      const documentsDir = await path.documentDir();
+      const { templatesDir } = await getDataLocation();
       const structureFile = await path.join(
-        documentsDir,
-        "FileArchitect",
-        "Templates",
+        templatesDir,
         `${structureName}.txt`
       );
       await invoke("reveal_file_command", { path: structureFile });
@@ -1,3 +1,4 @@
+import { getDataLocation } from "@/api/dataLocation";
 import { fs } from "@/features/structureEditor";
 import { Structure } from "@/types";
 import { path } from "@tauri-apps/api";
@@ -141,8 +142,8 @@ export function useStructureManagement() {
 
   // Note: We keep the disk folder as "Templates" for backward compatibility
   const getTemplatesDir = useCallback(async () => {
-    const documentsDir = await path.documentDir();
-    return await path.join(documentsDir, "FileArchitect", "Templates");
+    const { templatesDir } = await getDataLocation();
+    return templatesDir;
   }, []);
 
   const ensureTemplatesDir = useCallback(async () => {